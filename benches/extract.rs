@@ -0,0 +1,44 @@
+//! Headline extraction over synthetic pages shaped like the built-in sources:
+//! many cards, each a link wrapping nested title markup, among page chrome.
+//!
+//! Run with `cargo bench --bench extract`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use scraper::Selector;
+use scraper_cli::extract::{extract_headlines, HeadlineFilter};
+
+/// A page with `cards` headline cards, each split over several text nodes.
+fn page(cards: usize) -> String {
+    let mut html = String::from("<html><head><title>News</title></head><body><nav>");
+    for i in 0..50 {
+        html.push_str(&format!("<a href=\"/section/{}\">Section {}</a>", i, i));
+    }
+    html.push_str("</nav><main>");
+    for i in 0..cards {
+        html.push_str(&format!(
+            "<article><a href=\"/story/{i}\"><h2 class=\"headline\">\n  <span>Markets</span> \
+             rally as <em>rates</em>\n  fall for the {i}th time &amp; traders cheer\n</h2></a>\
+             <p>{}</p></article>",
+            "Lorem ipsum dolor sit amet. ".repeat(20)
+        ));
+    }
+    html.push_str("</main></body></html>");
+    html
+}
+
+fn bench_extract(c: &mut Criterion) {
+    let selectors = vec![(Selector::parse("h2.headline").unwrap(), None)];
+    let filter = HeadlineFilter::default();
+    let mut group = c.benchmark_group("extract_headlines");
+    for cards in [100, 1_000, 5_000] {
+        let html = page(cards);
+        group.throughput(criterion::Throughput::Bytes(html.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(cards), &html, |b, html| {
+            b.iter(|| extract_headlines(black_box(html), &selectors, &filter, None))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_extract);
+criterion_main!(benches);
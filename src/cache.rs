@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use tracing::{debug, warn};
+
+/// Response headers used to revalidate a stale entry with a conditional request.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// On-disk cache of raw page bodies, keyed by a hash of the URL.
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+    /// Ignore existing entries but still store fresh responses
+    refresh: bool,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration, refresh: bool) -> Self {
+        Cache {
+            dir: dir.into(),
+            ttl,
+            refresh,
+        }
+    }
+
+    /// Return the cached body for `url` if an entry younger than the TTL exists.
+    pub fn get(&self, url: &str) -> Option<String> {
+        if self.refresh {
+            return None;
+        }
+        let path = self.path_for(url);
+        let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age > self.ttl {
+            debug!(age_secs = age.as_secs(), "cache entry is stale");
+            return None;
+        }
+        let body = fs::read_to_string(&path).ok()?;
+        debug!(path = %path.display(), "serving page from cache");
+        Some(body)
+    }
+
+    /// Return the cached body for `url` regardless of age, with the
+    /// validators needed to revalidate it. Entries stored without validators
+    /// cannot be revalidated and are ignored.
+    pub fn stale(&self, url: &str) -> Option<(String, Validators)> {
+        if self.refresh {
+            return None;
+        }
+        let meta = fs::read_to_string(self.path_for(url).with_extension("meta")).ok()?;
+        let validators: Validators = serde_json::from_str(&meta).ok()?;
+        if validators.is_empty() {
+            return None;
+        }
+        let body = fs::read_to_string(self.path_for(url)).ok()?;
+        Some((body, validators))
+    }
+
+    /// Store `body` for `url` along with its validators, resetting the
+    /// entry's age. Failures are logged rather than aborting the scrape.
+    pub fn put(&self, url: &str, body: &str, validators: &Validators) {
+        let path = self.path_for(url);
+        let meta = serde_json::to_string(validators).unwrap_or_default();
+        let written = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&path, body))
+            .and_then(|_| fs::write(path.with_extension("meta"), meta));
+        if let Err(e) = written {
+            warn!("Failed to write cache entry {}: {}", path.display(), e);
+        }
+    }
+
+    fn path_for(&self, url: &str) -> PathBuf {
+        self.dir
+            .join(format!("{:016x}.html", fnv1a(url.as_bytes())))
+    }
+}
+
+/// 64-bit FNV-1a; unlike `DefaultHasher` it is stable across Rust releases,
+/// so cache file names survive toolchain upgrades.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,66 @@
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Stops sending requests to a host once it has failed `threshold` times in
+/// a row; the circuit stays open for the rest of the run.
+pub struct CircuitBreaker {
+    threshold: u32,
+    /// Consecutive failures per host, reset by any success
+    failures: Mutex<HashMap<String, u32>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(threshold: u32) -> Self {
+        CircuitBreaker {
+            threshold,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `url`'s host, if requests to it have been cut off.
+    pub fn open_host(&self, url: &str) -> Option<String> {
+        let host = host(url)?;
+        let failures = self.failures.lock().unwrap();
+        (failures.get(&host).copied().unwrap_or(0) >= self.threshold).then_some(host)
+    }
+
+    /// Note the outcome of a request to `url`'s host.
+    pub fn record(&self, url: &str, failed: bool) {
+        let Some(host) = host(url) else {
+            return;
+        };
+        let mut failures = self.failures.lock().unwrap();
+        if failed {
+            *failures.entry(host).or_insert(0) += 1;
+        } else {
+            failures.remove(&host);
+        }
+    }
+}
+
+fn host(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures_only() {
+        let breaker = CircuitBreaker::new(2);
+        breaker.record("https://a.com/1", true);
+        breaker.record("https://a.com/2", false);
+        breaker.record("https://a.com/3", true);
+        assert_eq!(breaker.open_host("https://a.com/"), None);
+        breaker.record("https://a.com/4", true);
+        assert_eq!(
+            breaker.open_host("https://a.com/5"),
+            Some("a.com".to_string())
+        );
+        assert_eq!(breaker.open_host("https://b.com/"), None);
+    }
+}
@@ -0,0 +1,197 @@
+use serde::{Deserialize, Deserializer};
+use std::fs;
+
+use crate::extract::DEFAULT_UNWANTED_HEADLINES;
+use crate::ScraperError;
+
+/// Sources analyzed by `--all` when no config file overrides them.
+const SOURCES: &[&str] = &[
+    "https://www.nytimes.com",
+    "https://www.theguardian.com",
+    "https://www.bbc.com",
+    "https://www.nature.com",
+    "https://www.economist.com",
+    "https://news.google.com",
+];
+
+/// Built-in `(match_domain, selector, attribute)` rules.
+const SELECTORS: &[(&str, &str, Option<&str>)] = &[
+    ("nytimes.com", "p.indicate-hover", None),
+    ("theguardian.com", "a.dcr-lv2v9o", Some("aria-label")),
+    ("bbc.com", "h2[data-testid='card-headline']", None),
+    ("nature.com", "a.c-card__link", None),
+    ("economist.com", "a[data-analytics]", None),
+    ("news.google.com", "a.gPFEn", None),
+];
+
+/// Source definitions loaded from a TOML file passed via `--config`.
+///
+/// ```toml
+/// sources = ["https://www.bbc.com"]
+/// # Exact titles to drop on every source; replaces the built-in list
+/// unwanted_headlines = ["Spelling Bee", "The Crossword"]
+///
+/// [[selectors]]
+/// match_domain = "bbc.com"
+/// selector = "h2[data-testid='card-headline']"
+///
+/// [[selectors]]
+/// match_domain = "nature.com"
+/// # Several selectors are all extracted and their matches merged
+/// selector = ["a.c-card__link", "h3.c-card__title"]
+/// # Tried one at a time, in order, only if the selectors above match nothing
+/// fallback_selectors = ["article h3 a"]
+///
+/// [[selectors]]
+/// match_domain = "theguardian.com"
+/// selector = "a.dcr-lv2v9o"
+/// attribute = "aria-label"
+///
+/// [[selectors]]
+/// match_domain = "nytimes.com"
+/// selector = "p.indicate-hover"
+/// # Appended to the global list for this source only
+/// unwanted_headlines = ["Connections Companion"]
+/// ```
+///
+/// Any top-level key may be omitted, in which case the built-in value is used.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    /// URLs analyzed by `--all`
+    #[serde(default = "default_sources")]
+    pub sources: Vec<String>,
+    /// Selector rules, matched against the URL in order
+    #[serde(default = "default_selectors")]
+    pub selectors: Vec<SourceConfig>,
+    /// Exact headline titles dropped from every source
+    #[serde(default = "default_unwanted_headlines")]
+    pub unwanted_headlines: Vec<String>,
+}
+
+/// How to find headlines on pages whose URL contains `match_domain`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct SourceConfig {
+    pub match_domain: String,
+    /// One selector or a list of them, all of which are extracted
+    #[serde(deserialize_with = "one_or_many")]
+    pub selector: Vec<String>,
+    /// Tried in order when `selector` matches nothing; the first to match wins
+    #[serde(default)]
+    pub fallback_selectors: Vec<String>,
+    /// Read the headline from this attribute instead of the element text
+    pub attribute: Option<String>,
+    /// Exact headline titles dropped from this source, on top of the global list
+    #[serde(default)]
+    pub unwanted_headlines: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            sources: default_sources(),
+            selectors: default_selectors(),
+            unwanted_headlines: default_unwanted_headlines(),
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &str) -> Result<Self, ScraperError> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| ScraperError::ConfigError(format!("{}: {}", path, e)))?;
+        toml::from_str(&contents).map_err(|e| ScraperError::ConfigError(format!("{}: {}", path, e)))
+    }
+
+    /// Find the first selector rule whose domain appears in `url`.
+    pub fn source_for(&self, url: &str) -> Option<&SourceConfig> {
+        self.selectors
+            .iter()
+            .find(|source| url.contains(&source.match_domain))
+    }
+}
+
+/// Parse a newline-delimited list, skipping blank lines and `#` comments.
+pub fn parse_line_list(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Accept either a single string or a list of strings.
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(selector) => vec![selector],
+        OneOrMany::Many(selectors) => selectors,
+    })
+}
+
+fn default_sources() -> Vec<String> {
+    SOURCES.iter().map(|source| source.to_string()).collect()
+}
+
+fn default_selectors() -> Vec<SourceConfig> {
+    SELECTORS
+        .iter()
+        .map(|(match_domain, selector, attribute)| SourceConfig {
+            match_domain: match_domain.to_string(),
+            selector: vec![selector.to_string()],
+            attribute: attribute.map(str::to_string),
+            ..SourceConfig::default()
+        })
+        .collect()
+}
+
+fn default_unwanted_headlines() -> Vec<String> {
+    DEFAULT_UNWANTED_HEADLINES
+        .iter()
+        .map(|title| title.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_list_skips_blank_lines_and_comments() {
+        let contents = "# news\nhttps://a.example\n\n  https://b.example  \n#https://c.example\n";
+        assert_eq!(
+            parse_line_list(contents),
+            vec!["https://a.example", "https://b.example"]
+        );
+    }
+
+    #[test]
+    fn omitted_keys_fall_back_to_builtins() {
+        let config: Config = toml::from_str(
+            r#"
+            [[selectors]]
+            match_domain = "example.com"
+            selector = "h1"
+            unwanted_headlines = ["Sign up"]
+
+            [[selectors]]
+            match_domain = "example.org"
+            selector = ["h1", "h2"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.sources, default_sources());
+        assert_eq!(config.unwanted_headlines, default_unwanted_headlines());
+        let source = config.source_for("https://example.com/news").unwrap();
+        assert_eq!(source.unwanted_headlines, vec!["Sign up"]);
+        assert_eq!(source.selector, vec!["h1"]);
+        let source = config.source_for("https://example.org").unwrap();
+        assert_eq!(source.selector, vec!["h1", "h2"]);
+        assert!(config.source_for("https://bbc.com").is_none());
+    }
+}
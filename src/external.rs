@@ -0,0 +1,108 @@
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::ScraperError;
+
+/// An external program that extracts headlines from pages static scraping
+/// can't handle, such as a headless-browser helper. It is run through the
+/// shell with the page on stdin and `SCRAPER_URL` set to the page's address,
+/// and prints one headline per line on stdout.
+pub struct ExternalExtractor {
+    command: String,
+    /// How long the program may run before it is killed
+    timeout: Duration,
+}
+
+impl ExternalExtractor {
+    pub fn new(command: impl Into<String>, timeout: Duration) -> Self {
+        ExternalExtractor {
+            command: command.into(),
+            timeout,
+        }
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// Pipe `html` fetched from `url` through the command and return what it
+    /// printed.
+    pub async fn run(&self, html: &str, url: &str) -> Result<String, ScraperError> {
+        let failed = |reason: String| {
+            ScraperError::ExtractorError(format!("`{}` on {}: {}", self.command, url, reason))
+        };
+        let mut child = shell(&self.command)
+            .env("SCRAPER_URL", url)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| failed(e.to_string()))?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        let html = html.as_bytes().to_vec();
+        // Write concurrently with reading, so a large page can't fill the
+        // pipes and deadlock. A program that ignores its input may close
+        // stdin early, which isn't an error.
+        let write = async move {
+            let _ = stdin.write_all(&html).await;
+        };
+        let (_, output) = tokio::time::timeout(self.timeout, async {
+            tokio::join!(write, child.wait_with_output())
+        })
+        .await
+        .map_err(|_| failed(format!("timed out after {}s", self.timeout.as_secs_f64())))?;
+        let output = output.map_err(|e| failed(e.to_string()))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stderr = stderr.trim();
+            return Err(failed(if stderr.is_empty() {
+                output.status.to_string()
+            } else {
+                format!("{}: {}", output.status, stderr)
+            }));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failures_and_timeouts_are_errors() {
+        let timeout = Duration::from_secs(5);
+        let echo = ExternalExtractor::new("tr a-z A-Z; echo \"$SCRAPER_URL\"", timeout);
+        assert_eq!(
+            echo.run("story\n", "https://example.com").await.unwrap(),
+            "STORY\nhttps://example.com\n"
+        );
+
+        let failing = ExternalExtractor::new("echo no browser >&2; exit 3", timeout);
+        let error = failing.run("", "https://example.com").await.unwrap_err();
+        assert!(error.to_string().contains("no browser"), "{}", error);
+
+        let slow = ExternalExtractor::new("sleep 5", Duration::from_millis(100));
+        let error = slow.run("", "https://example.com").await.unwrap_err();
+        assert!(error.to_string().contains("timed out"), "{}", error);
+    }
+}
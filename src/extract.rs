@@ -0,0 +1,608 @@
+use clap::ValueEnum;
+use reqwest::Url;
+use scraper::{ElementRef, Html, Selector};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+use tracing::warn;
+
+/// Specific unwanted headlines that match the NYT selector but aren't news.
+pub const DEFAULT_UNWANTED_HEADLINES: &[&str] =
+    &["Connections Companion", "Spelling Bee", "The Crossword"];
+
+/// Where headlines are read from on a page.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum Strategy {
+    /// Elements matched by the source's CSS selectors
+    Css,
+    /// `headline`/`name` of article objects in `application/ld+json` scripts
+    Jsonld,
+    /// Item titles of an RSS or Atom feed
+    Feed,
+}
+
+/// A headline and the article it links to, when one could be found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Headline {
+    pub text: String,
+    /// Absolute article URL
+    pub link: Option<String>,
+}
+
+/// Rules deciding which extracted candidates count as headlines.
+#[derive(Clone, Debug)]
+pub struct HeadlineFilter {
+    /// Headlines with fewer words than this are dropped
+    pub min_words: usize,
+    /// Headlines with more words than this are dropped
+    pub max_words: Option<usize>,
+    /// When non-empty, only headlines containing one of these terms are kept
+    pub include: Vec<String>,
+    /// Headlines containing any of these terms are dropped
+    pub exclude: Vec<String>,
+    /// Headlines exactly matching one of these titles are dropped
+    pub unwanted: Vec<String>,
+    /// Decode HTML entities such as `&amp;` left in the text before filtering
+    pub decode_entities: bool,
+    /// Log each rejected candidate and why it was dropped
+    pub show_rejected: bool,
+    /// Apply the rules above; when off, every non-empty candidate is kept
+    pub enabled: bool,
+}
+
+impl Default for HeadlineFilter {
+    fn default() -> Self {
+        HeadlineFilter {
+            min_words: 2,
+            max_words: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            unwanted: DEFAULT_UNWANTED_HEADLINES
+                .iter()
+                .map(|title| title.to_string())
+                .collect(),
+            decode_entities: true,
+            show_rejected: false,
+            enabled: true,
+        }
+    }
+}
+
+/// Why a candidate was dropped by a `HeadlineFilter`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Rejection {
+    TooFewWords { words: usize, min: usize },
+    TooManyWords { words: usize, max: usize },
+    Unwanted,
+    NotIncluded,
+    Excluded(String),
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Rejection::TooFewWords { words, min } => {
+                write!(f, "{} words, fewer than the minimum of {}", words, min)
+            }
+            Rejection::TooManyWords { words, max } => {
+                write!(f, "{} words, more than the maximum of {}", words, max)
+            }
+            Rejection::Unwanted => write!(f, "listed as an unwanted headline"),
+            Rejection::NotIncluded => write!(f, "contains no --include term"),
+            Rejection::Excluded(term) => write!(f, "contains excluded term \"{}\"", term),
+        }
+    }
+}
+
+impl HeadlineFilter {
+    /// The first rule `text` breaks, or `None` if it should be kept.
+    fn rejection(&self, text: &str) -> Option<Rejection> {
+        let words = text.split_whitespace().count();
+        let min = if self.enabled { self.min_words } else { 1 };
+        if words < min {
+            return Some(Rejection::TooFewWords { words, min });
+        }
+        if !self.enabled {
+            return None;
+        }
+        if let Some(max) = self.max_words.filter(|&max| words > max) {
+            return Some(Rejection::TooManyWords { words, max });
+        }
+        if self.unwanted.iter().any(|title| title == text) {
+            return Some(Rejection::Unwanted);
+        }
+        self.keyword_rejection(text)
+    }
+
+    /// Case-insensitive substring checks against `include` and `exclude`.
+    fn keyword_rejection(&self, text: &str) -> Option<Rejection> {
+        let text = text.to_lowercase();
+        let contains = |term: &&String| text.contains(&term.to_lowercase());
+        if !self.include.is_empty() && !self.include.iter().any(|term| contains(&term)) {
+            return Some(Rejection::NotIncluded);
+        }
+        self.exclude
+            .iter()
+            .find(contains)
+            .map(|term| Rejection::Excluded(term.clone()))
+    }
+}
+
+/// Pull headline text out of `html` using each `(selector, attribute)` pair in
+/// turn, reading the attribute instead of the element text when given. An
+/// element matched by several selectors is only extracted once. Links are
+/// resolved against `base_url`; without one only absolute hrefs are kept.
+pub fn extract_headlines(
+    html: &str,
+    selectors: &[(Selector, Option<String>)],
+    filter: &HeadlineFilter,
+    base_url: Option<&Url>,
+) -> Vec<Headline> {
+    let document = Html::parse_document(html);
+    let mut seen = HashSet::new();
+    // Reused across candidates; most are rejected, so most never need their own
+    let mut text = String::new();
+    selectors
+        .iter()
+        .flat_map(|(selector, attribute)| {
+            document
+                .select(selector)
+                .map(move |element| (element, attribute.as_deref()))
+        })
+        .filter(|(element, _)| seen.insert(element.id()))
+        .filter_map(|(element, attribute)| {
+            text.clear();
+            match attribute {
+                Some(attr) => push_words(&mut text, element.value().attr(attr).unwrap_or("")),
+                // Text nodes are joined with a space, as block elements would be
+                None => element
+                    .text()
+                    .for_each(|fragment| push_words(&mut text, fragment)),
+            }
+            accept_headline(&text, filter).map(|text| Headline {
+                text,
+                link: article_link(element, base_url),
+            })
+        })
+        .collect()
+}
+
+/// Pull headlines out of the page's JSON-LD blocks: the `headline` (or
+/// failing that `name`) of every object whose `@type` is an article, found
+/// anywhere in the structure, e.g. inside `@graph` or an `ItemList`.
+pub fn extract_jsonld_headlines(
+    html: &str,
+    filter: &HeadlineFilter,
+    base_url: Option<&Url>,
+) -> Vec<Headline> {
+    let document = Html::parse_document(html);
+    let scripts = Selector::parse(r#"script[type="application/ld+json"]"#).unwrap();
+    let mut headlines = Vec::new();
+    for script in document.select(&scripts) {
+        // Malformed blocks are common enough that one shouldn't sink the page
+        if let Ok(data) = serde_json::from_str::<Value>(&script.text().collect::<String>()) {
+            collect_articles(&data, filter, base_url, &mut headlines);
+        }
+    }
+    headlines
+}
+
+/// Pull item titles and links out of an RSS, Atom or JSON feed.
+pub fn extract_feed_headlines(
+    body: &str,
+    filter: &HeadlineFilter,
+    base_url: Option<&Url>,
+) -> Result<Vec<Headline>, feed_rs::parser::ParseFeedError> {
+    let feed = feed_rs::parser::parse(body.as_bytes())?;
+    Ok(feed
+        .entries
+        .into_iter()
+        .filter_map(|entry| {
+            let text = clean_headline(&entry.title?.content, filter)?;
+            let link = entry
+                .links
+                .first()
+                .and_then(|link| resolve_link(&link.href, base_url));
+            Some(Headline { text, link })
+        })
+        .collect())
+}
+
+/// Headlines printed one per line, as by an external extractor.
+pub fn extract_lines(output: &str, filter: &HeadlineFilter) -> Vec<Headline> {
+    output
+        .lines()
+        .filter_map(|line| clean_headline(line, filter))
+        .map(|text| Headline { text, link: None })
+        .collect()
+}
+
+fn collect_articles(
+    value: &Value,
+    filter: &HeadlineFilter,
+    base_url: Option<&Url>,
+    headlines: &mut Vec<Headline>,
+) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                collect_articles(item, filter, base_url, headlines);
+            }
+        }
+        Value::Object(object) => {
+            if is_article(object.get("@type")) {
+                let title = object
+                    .get("headline")
+                    .or_else(|| object.get("name"))
+                    .and_then(Value::as_str);
+                if let Some(text) = title.and_then(|title| clean_headline(title, filter)) {
+                    let link = object
+                        .get("url")
+                        .and_then(Value::as_str)
+                        .and_then(|href| resolve_link(href, base_url));
+                    headlines.push(Headline { text, link });
+                }
+            }
+            for child in object.values() {
+                collect_articles(child, filter, base_url, headlines);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `Article`, `NewsArticle`, `BlogPosting` and the other schema.org article
+/// subtypes; `@type` may be a single string or a list.
+fn is_article(kind: Option<&Value>) -> bool {
+    let matches = |kind: &Value| {
+        kind.as_str()
+            .is_some_and(|kind| kind.ends_with("Article") || kind == "BlogPosting")
+    };
+    match kind {
+        Some(Value::Array(kinds)) => kinds.iter().any(matches),
+        Some(kind) => matches(kind),
+        None => false,
+    }
+}
+
+/// Normalize raw headline text and apply the filter, returning the cleaned
+/// text if it should be kept.
+fn clean_headline(raw: &str, filter: &HeadlineFilter) -> Option<String> {
+    let mut text = String::with_capacity(raw.len());
+    push_words(&mut text, raw);
+    accept_headline(&text, filter)
+}
+
+/// Decode and filter whitespace-normalized text, only allocating the result
+/// for headlines that are kept.
+fn accept_headline(text: &str, filter: &HeadlineFilter) -> Option<String> {
+    let text = if filter.decode_entities {
+        html_escape::decode_html_entities(text)
+    } else {
+        Cow::Borrowed(text)
+    };
+    // Filter out too-short, too-long and unwanted headlines
+    match filter.rejection(&text) {
+        None => Some(text.into_owned()),
+        Some(reason) => {
+            if filter.show_rejected {
+                warn!(headline = %text, %reason, "filtered out");
+            }
+            None
+        }
+    }
+}
+
+/// The href of the element itself or its nearest enclosing link, made absolute.
+fn article_link(element: ElementRef, base_url: Option<&Url>) -> Option<String> {
+    let href = std::iter::once(element)
+        .chain(element.ancestors().filter_map(ElementRef::wrap))
+        .find_map(|el| {
+            (el.value().name() == "a")
+                .then(|| el.value().attr("href"))
+                .flatten()
+        })?;
+    resolve_link(href, base_url)
+}
+
+fn resolve_link(href: &str, base_url: Option<&Url>) -> Option<String> {
+    let link = match base_url {
+        Some(base) => base.join(href),
+        None => Url::parse(href),
+    };
+    link.ok().map(String::from)
+}
+
+/// Append the words of `text` to `buffer`, separated by single spaces, so
+/// runs of whitespace (including non-breaking spaces) collapse and the ends
+/// are trimmed.
+fn push_words(buffer: &mut String, text: &str) {
+    // `split_whitespace` already treats U+00A0 as whitespace
+    for word in text.split_whitespace() {
+        if !buffer.is_empty() {
+            buffer.push(' ');
+        }
+        buffer.push_str(word);
+    }
+}
+
+/// Drop repeated headlines, preserving first-seen order.
+pub fn dedupe_headlines(headlines: Vec<Headline>, ignore_case: bool) -> Vec<Headline> {
+    let mut seen = HashSet::new();
+    headlines
+        .into_iter()
+        .filter(|headline| {
+            let key = if ignore_case {
+                headline.text.to_lowercase()
+            } else {
+                headline.text.clone()
+            };
+            seen.insert(key)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extract(html: &str, selector: &str, attribute: Option<&str>) -> Vec<String> {
+        texts(extract_headlines(
+            html,
+            &rule(selector, attribute),
+            &HeadlineFilter::default(),
+            None,
+        ))
+    }
+
+    fn rule(selector: &str, attribute: Option<&str>) -> Vec<(Selector, Option<String>)> {
+        vec![(
+            Selector::parse(selector).unwrap(),
+            attribute.map(str::to_string),
+        )]
+    }
+
+    fn texts(headlines: Vec<Headline>) -> Vec<String> {
+        headlines
+            .into_iter()
+            .map(|headline| headline.text)
+            .collect()
+    }
+
+    fn headline(text: &str) -> Headline {
+        Headline {
+            text: text.to_string(),
+            link: None,
+        }
+    }
+
+    #[test]
+    fn rejections_name_the_rule_that_was_broken() {
+        let filter = HeadlineFilter {
+            max_words: Some(4),
+            include: vec!["vote".to_string()],
+            exclude: vec!["Poll".to_string()],
+            ..HeadlineFilter::default()
+        };
+        assert_eq!(
+            filter.rejection("Breaking"),
+            Some(Rejection::TooFewWords { words: 1, min: 2 })
+        );
+        assert_eq!(
+            filter.rejection("Council vote delayed again until spring"),
+            Some(Rejection::TooManyWords { words: 6, max: 4 })
+        );
+        assert_eq!(filter.rejection("The Crossword"), Some(Rejection::Unwanted));
+        assert_eq!(
+            filter.rejection("Markets rally today"),
+            Some(Rejection::NotIncluded)
+        );
+        assert_eq!(
+            filter.rejection("Vote poll tightens"),
+            Some(Rejection::Excluded("Poll".to_string()))
+        );
+        assert_eq!(filter.rejection("Council vote delayed"), None);
+
+        let disabled = HeadlineFilter {
+            enabled: false,
+            ..filter
+        };
+        assert_eq!(disabled.rejection("Breaking"), None);
+        assert_eq!(disabled.rejection("The Crossword"), None);
+        assert_eq!(
+            disabled.rejection(""),
+            Some(Rejection::TooFewWords { words: 0, min: 1 })
+        );
+    }
+
+    #[test]
+    fn drops_one_word_headlines() {
+        let html = "<h2>Breaking</h2><h2>Markets rally today</h2>";
+        assert_eq!(extract(html, "h2", None), vec!["Markets rally today"]);
+    }
+
+    #[test]
+    fn drops_unwanted_headlines() {
+        let html = "<p>Spelling Bee</p><p>The Crossword</p><p>Storm hits coast</p>";
+        assert_eq!(extract(html, "p", None), vec!["Storm hits coast"]);
+    }
+
+    #[test]
+    fn reads_attribute_when_given() {
+        let html = r#"<a aria-label="Election results are in">Read more</a>"#;
+        assert_eq!(
+            extract(html, "a", Some("aria-label")),
+            vec!["Election results are in"]
+        );
+    }
+
+    #[test]
+    fn skips_elements_missing_the_attribute() {
+        let html = r#"<a>No label here</a><a aria-label="Labelled link text">x</a>"#;
+        assert_eq!(
+            extract(html, "a", Some("aria-label")),
+            vec!["Labelled link text"]
+        );
+    }
+
+    #[test]
+    fn trims_surrounding_whitespace() {
+        let html = "<h2>\n   Rain expected tomorrow  \n</h2>";
+        assert_eq!(extract(html, "h2", None), vec!["Rain expected tomorrow"]);
+    }
+
+    #[test]
+    fn collapses_internal_whitespace() {
+        let html = "<h2>Rain\n\t expected\u{00a0}\u{00a0}tomorrow</h2>";
+        assert_eq!(extract(html, "h2", None), vec!["Rain expected tomorrow"]);
+    }
+
+    #[test]
+    fn decodes_leftover_entities_unless_disabled() {
+        let selector = rule("a", Some("title"));
+        let html = r#"<a title="Tom &amp;amp; Jerry return">x</a>"#;
+        assert_eq!(
+            texts(extract_headlines(
+                html,
+                &selector,
+                &HeadlineFilter::default(),
+                None
+            )),
+            vec!["Tom & Jerry return"]
+        );
+        let filter = HeadlineFilter {
+            decode_entities: false,
+            ..HeadlineFilter::default()
+        };
+        assert_eq!(
+            texts(extract_headlines(html, &selector, &filter, None)),
+            vec!["Tom &amp; Jerry return"]
+        );
+    }
+
+    #[test]
+    fn joins_text_across_child_elements() {
+        let html = "<h2><span>Local team</span><em>wins final</em></h2>";
+        assert_eq!(extract(html, "h2", None), vec!["Local team wins final"]);
+    }
+
+    #[test]
+    fn applies_word_bounds() {
+        let selector = rule("p", None);
+        let filter = HeadlineFilter {
+            min_words: 1,
+            max_words: Some(3),
+            ..HeadlineFilter::default()
+        };
+        let html = "<p>Short</p><p>This one is far too long</p><p>Just right here</p>";
+        assert_eq!(
+            texts(extract_headlines(html, &selector, &filter, None)),
+            vec!["Short", "Just right here"]
+        );
+    }
+
+    #[test]
+    fn include_and_exclude_match_case_insensitively() {
+        let selector = rule("p", None);
+        let filter = HeadlineFilter {
+            include: vec!["climate".to_string()],
+            exclude: vec!["OPINION".to_string()],
+            ..HeadlineFilter::default()
+        };
+        let html =
+            "<p>Climate talks resume</p><p>Opinion: climate fatigue</p><p>Sports roundup today</p>";
+        assert_eq!(
+            texts(extract_headlines(html, &selector, &filter, None)),
+            vec!["Climate talks resume"]
+        );
+    }
+
+    #[test]
+    fn merges_selectors_without_repeating_elements() {
+        let mut selectors = rule("h2", None);
+        selectors.extend(rule("h2, h3", None));
+        let html = "<h2>Main story here</h2><h3>Secondary story here</h3>";
+        assert_eq!(
+            texts(extract_headlines(
+                html,
+                &selectors,
+                &HeadlineFilter::default(),
+                None
+            )),
+            vec!["Main story here", "Secondary story here"]
+        );
+    }
+
+    #[test]
+    fn reads_article_headlines_from_json_ld() {
+        let html = r#"<script type="application/ld+json">
+            {"@context": "https://schema.org", "@graph": [
+                {"@type": "WebSite", "name": "Example News Site"},
+                {"@type": ["NewsArticle"], "headline": "Bridge reopens after repairs",
+                 "url": "/local/bridge"},
+                {"@type": "ItemList", "itemListElement": [
+                    {"@type": "ListItem", "item": {"@type": "Article", "name": "Schools plan new term"}}
+                ]}
+            ]}
+            </script>
+            <script type="application/ld+json">{ not json</script>"#;
+        let base = Url::parse("https://news.example/").unwrap();
+        let headlines = extract_jsonld_headlines(html, &HeadlineFilter::default(), Some(&base));
+        assert_eq!(
+            texts(headlines.clone()),
+            vec!["Bridge reopens after repairs", "Schools plan new term"]
+        );
+        assert_eq!(
+            headlines[0].link.as_deref(),
+            Some("https://news.example/local/bridge")
+        );
+    }
+
+    #[test]
+    fn reads_rss_item_titles() {
+        let rss = r#"<?xml version="1.0"?>
+            <rss version="2.0"><channel><title>Example feed</title>
+                <item><title>Harbour &amp; docks reopen</title><link>https://news.example/a</link></item>
+                <item><title>Solo</title></item>
+            </channel></rss>"#;
+        let headlines = extract_feed_headlines(rss, &HeadlineFilter::default(), None).unwrap();
+        assert_eq!(
+            headlines,
+            vec![Headline {
+                text: "Harbour & docks reopen".to_string(),
+                link: Some("https://news.example/a".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn dedupe_keeps_first_occurrence() {
+        let headlines = vec![headline("A b"), headline("C d"), headline("A b")];
+        assert_eq!(
+            texts(dedupe_headlines(headlines, false)),
+            vec!["A b", "C d"]
+        );
+    }
+
+    #[test]
+    fn dedupe_can_ignore_case() {
+        let headlines = vec![headline("Big News"), headline("BIG NEWS")];
+        assert_eq!(dedupe_headlines(headlines.clone(), false).len(), 2);
+        assert_eq!(texts(dedupe_headlines(headlines, true)), vec!["Big News"]);
+    }
+
+    #[test]
+    fn resolves_links_from_the_element_or_its_ancestors() {
+        let selector = rule("h2", None);
+        let base = Url::parse("https://news.example/world/").unwrap();
+        let html = r#"<a href="../story-1"><h2>Nested in link</h2></a>
+            <h2>No link at all</h2>"#;
+        let headlines = extract_headlines(html, &selector, &HeadlineFilter::default(), Some(&base));
+        assert_eq!(
+            headlines[0].link.as_deref(),
+            Some("https://news.example/story-1")
+        );
+        assert_eq!(headlines[1].link, None);
+    }
+}
@@ -0,0 +1,585 @@
+use clap::ValueEnum;
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use rand::Rng;
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::{
+    HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+    USER_AGENT,
+};
+use reqwest::StatusCode;
+use reqwest::{Client, Response, Url};
+use std::fs;
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::cache::{Cache, Validators};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::external::ExternalExtractor;
+use crate::extract::{HeadlineFilter, Strategy};
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::random::SharedRng;
+use crate::rate_limit::RateLimiter;
+use crate::robots::RobotsCache;
+use crate::{ScraperError, DEFAULT_USER_AGENT};
+
+/// Current desktop and mobile browsers, cycled through by `--user-agent-rotate`.
+const USER_AGENT_POOL: &[&str] = &[
+    DEFAULT_USER_AGENT,
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Safari/605.1.15",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:125.0) Gecko/20100101 Firefox/125.0",
+    "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36 Edg/124.0.0.0",
+    "Mozilla/5.0 (iPhone; CPU iPhone OS 17_4 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.4 Mobile/15E148 Safari/604.1",
+];
+
+/// Hands out User-Agent strings round-robin, one per request.
+pub struct UserAgentPool {
+    agents: Vec<String>,
+    next: AtomicUsize,
+}
+
+impl UserAgentPool {
+    /// The built-in pool, led by `preferred` when it is not already in it.
+    pub fn new(preferred: &str) -> Self {
+        let mut agents: Vec<String> = USER_AGENT_POOL.iter().map(|ua| ua.to_string()).collect();
+        if !agents.iter().any(|ua| ua == preferred) {
+            agents.insert(0, preferred.to_string());
+        }
+        UserAgentPool {
+            agents,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn next(&self) -> &str {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.agents.len();
+        &self.agents[index]
+    }
+}
+
+/// A cookie store that sends a fixed set of `name=value` cookies to every
+/// host on top of whatever the sites themselves set.
+pub struct FixedCookies {
+    jar: Jar,
+    fixed: Vec<String>,
+}
+
+impl FixedCookies {
+    pub fn new(fixed: Vec<String>) -> Self {
+        FixedCookies {
+            jar: Jar::default(),
+            fixed,
+        }
+    }
+}
+
+impl CookieStore for FixedCookies {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        self.jar.set_cookies(cookie_headers, url);
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let stored = self.jar.cookies(url);
+        let stored = stored.as_ref().and_then(|header| header.to_str().ok());
+        let cookies: Vec<&str> = self
+            .fixed
+            .iter()
+            .map(String::as_str)
+            .chain(stored)
+            .collect();
+        if cookies.is_empty() {
+            return None;
+        }
+        HeaderValue::from_str(&cookies.join("; ")).ok()
+    }
+}
+
+/// How retry delays are randomized, so that fetches failing together don't
+/// all retry at the same moment. Both draw from the run's seeded RNG.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum Jitter {
+    /// Wait exactly the exponential backoff
+    None,
+    /// Wait a uniformly random time between zero and the backoff
+    Full,
+    /// Wait half the backoff plus a uniformly random time up to the other
+    /// half, which keeps delays growing while still spreading them out
+    #[default]
+    Equal,
+}
+
+/// Fetching and filtering behavior shared by every source in a run.
+pub struct FetchOptions {
+    /// How many times a retriable failure is retried before giving up
+    pub retries: u32,
+    /// Delay before the first retry; doubled on each subsequent attempt
+    pub retry_delay: Duration,
+    pub retry_jitter: Jitter,
+    /// Skip URLs that the site's robots.txt disallows for `user_agent`
+    pub respect_robots: bool,
+    /// robots.txt rules already fetched this run
+    pub robots: RobotsCache,
+    pub user_agent: String,
+    pub filter: HeadlineFilter,
+    /// Drop repeated headlines, keeping the first occurrence
+    pub dedupe: bool,
+    /// Compare headlines case-insensitively when deduplicating
+    pub dedupe_ignore_case: bool,
+    /// Keep at most this many headlines per source
+    pub limit: Option<usize>,
+    /// Fail sources that yield fewer headlines than this, before `limit` applies
+    pub min_headlines: Option<usize>,
+    /// Keep a random sample of this many headlines per source, after `limit`
+    pub sample: Option<usize>,
+    /// Serve and store page bodies on disk when set
+    pub cache: Option<Cache>,
+    /// Enforces `--delay` between requests to the same host
+    pub rate_limiter: Option<RateLimiter>,
+    /// Skips hosts that keep failing when set
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// Re-fetch a page, up to `retries` times, when no headlines match
+    pub retry_on_empty: bool,
+    /// Abort downloads larger than this many bytes
+    pub max_body_size: u64,
+    pub strategy: Strategy,
+    /// Send a different User-Agent with each page request when set
+    pub user_agents: Option<UserAgentPool>,
+    /// Accept URL schemes other than http and https
+    pub allow_local: bool,
+    /// Parse responses whatever their `Content-Type`, for mislabelled pages
+    pub force_html: bool,
+    /// Source of every random choice, shared with the rest of the run
+    pub rng: Arc<SharedRng>,
+    /// Extract headlines with this program instead of `strategy` when set
+    pub extractor: Option<ExternalExtractor>,
+    /// Collects request latencies and per-source outcomes when set
+    #[cfg(feature = "metrics")]
+    pub metrics: Option<Arc<Metrics>>,
+}
+
+impl Default for FetchOptions {
+    fn default() -> Self {
+        FetchOptions {
+            retries: 3,
+            retry_delay: Duration::from_millis(500),
+            retry_jitter: Jitter::default(),
+            respect_robots: true,
+            robots: RobotsCache::default(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            filter: HeadlineFilter::default(),
+            dedupe: true,
+            dedupe_ignore_case: false,
+            limit: None,
+            min_headlines: None,
+            sample: None,
+            cache: None,
+            rate_limiter: None,
+            circuit_breaker: None,
+            retry_on_empty: false,
+            max_body_size: 10 * 1024 * 1024,
+            strategy: Strategy::Css,
+            user_agents: None,
+            allow_local: false,
+            force_html: false,
+            rng: Arc::default(),
+            extractor: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+        }
+    }
+}
+
+impl FetchOptions {
+    /// Delay before retry number `attempt` (counting from zero).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .retry_delay
+            .saturating_mul(2u32.saturating_pow(attempt));
+        let random = || self.rng.with(|rng| rng.gen_range(0.0..=1.0));
+        match self.retry_jitter {
+            Jitter::None => delay,
+            Jitter::Full => delay.mul_f64(random()),
+            Jitter::Equal => delay / 2 + (delay / 2).mul_f64(random()),
+        }
+    }
+}
+
+/// A downloaded page, or `None` for the body when the server answered a
+/// conditional request with 304 Not Modified.
+struct Page {
+    body: Option<String>,
+    validators: Validators,
+}
+
+/// Download the page body at `url`, retrying timeouts and 5xx responses with
+/// exponential backoff. Unless disabled, robots.txt is consulted first.
+/// A fresh cache entry skips the network entirely, and a stale one is
+/// revalidated with `If-None-Match`/`If-Modified-Since`.
+pub async fn fetch_page(
+    client: &Client,
+    options: &FetchOptions,
+    url: &str,
+) -> Result<String, ScraperError> {
+    let Some(cache) = &options.cache else {
+        return refetch_page(client, options, url).await;
+    };
+    if let Some(body) = cache.get(url) {
+        return Ok(body);
+    }
+    let stale = cache.stale(url);
+    let page = fetch_page_uncached(client, options, url, stale.as_ref().map(|(_, v)| v)).await?;
+    match (page.body, stale) {
+        (Some(body), _) => {
+            cache.put(url, &body, &page.validators);
+            Ok(body)
+        }
+        (None, Some((body, validators))) => {
+            debug!("not modified; reusing cached body");
+            cache.put(url, &body, &validators);
+            Ok(body)
+        }
+        // Only conditional requests can come back 304
+        (None, None) => unreachable!("304 without a conditional request"),
+    }
+}
+
+/// Like [`fetch_page`], but always downloads the full page, refreshing the cache.
+pub async fn refetch_page(
+    client: &Client,
+    options: &FetchOptions,
+    url: &str,
+) -> Result<String, ScraperError> {
+    let page = fetch_page_uncached(client, options, url, None).await?;
+    let body = page.body.unwrap_or_default();
+    if let Some(cache) = &options.cache {
+        cache.put(url, &body, &page.validators);
+    }
+    Ok(body)
+}
+
+async fn fetch_page_uncached(
+    client: &Client,
+    options: &FetchOptions,
+    url: &str,
+    conditional: Option<&Validators>,
+) -> Result<Page, ScraperError> {
+    check_circuit(options, url)?;
+    if options.respect_robots {
+        let fetch = |robots_url: Url| fetch_robots(client, options, robots_url);
+        if !options
+            .robots
+            .is_allowed(url, &options.user_agent, fetch)
+            .await
+        {
+            return Err(ScraperError::DisallowedByRobots(url.to_string()));
+        }
+    }
+
+    let mut attempt = 0;
+    loop {
+        if let Some(rate_limiter) = &options.rate_limiter {
+            rate_limiter.wait(url).await;
+        }
+        // Checked before every attempt so retries stop once the circuit opens
+        check_circuit(options, url)?;
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let result = try_fetch_page(client, options, url, conditional).await;
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &options.metrics {
+            metrics.record_request(start.elapsed());
+        }
+        if let Some(breaker) = &options.circuit_breaker {
+            breaker.record(url, result.as_ref().is_err_and(is_host_failure));
+        }
+        match result {
+            Err(e) if attempt < options.retries && is_retriable(&e) => {
+                let delay = options.backoff(attempt);
+                attempt += 1;
+                warn!(
+                    "Retrying {} in {}ms (attempt {}/{}): {}",
+                    url,
+                    delay.as_millis(),
+                    attempt,
+                    options.retries,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Download a site's robots.txt, subject to `--delay` and the circuit breaker
+/// like any other request. `None` if it is missing or unreachable.
+async fn fetch_robots(client: &Client, options: &FetchOptions, robots_url: Url) -> Option<String> {
+    let url = robots_url.as_str();
+    if let Some(rate_limiter) = &options.rate_limiter {
+        rate_limiter.wait(url).await;
+    }
+    check_circuit(options, url).ok()?;
+    let result = client.get(robots_url.clone()).send().await;
+    // Only an unreachable host counts against it; a missing robots.txt is
+    // normal, and one success shouldn't reset the page fetches' failures
+    if let (Some(breaker), Err(_)) = (&options.circuit_breaker, &result) {
+        breaker.record(url, true);
+    }
+    let response = result.ok().filter(|r| r.status().is_success())?;
+    response.text().await.ok()
+}
+
+async fn try_fetch_page(
+    client: &Client,
+    options: &FetchOptions,
+    url: &str,
+    conditional: Option<&Validators>,
+) -> Result<Page, ScraperError> {
+    let start = Instant::now();
+    let mut request = client.get(url);
+    if let Some(pool) = &options.user_agents {
+        let user_agent = pool.next();
+        debug!(user_agent, "rotating User-Agent");
+        request = request.header(USER_AGENT, user_agent);
+    }
+    if let Some(validators) = conditional {
+        if let Some(etag) = &validators.etag {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+    let response = request.send().await.map_err(|e| request_error(url, e))?;
+    let status = response.status();
+    debug!(status = status.as_u16(), "received response headers");
+    if Url::parse(url).ok().as_ref() != Some(response.url()) {
+        info!(final_url = %response.url(), "followed redirects");
+    }
+
+    let validators = validators(response.headers());
+    if status == StatusCode::NOT_MODIFIED && conditional.is_some() {
+        return Ok(Page {
+            body: None,
+            validators,
+        });
+    }
+    if !status.is_success() {
+        return Err(ScraperError::HttpStatus {
+            url: url.to_string(),
+            code: status.as_u16(),
+        });
+    }
+    if !options.force_html {
+        check_content_type(url, response.headers(), options.strategy)?;
+    }
+    let body = read_body(response, url, options.max_body_size).await?;
+    info!(
+        status = status.as_u16(),
+        bytes = body.len(),
+        elapsed_ms = start.elapsed().as_millis() as u64,
+        "fetched page"
+    );
+    Ok(Page {
+        body: Some(body),
+        validators,
+    })
+}
+
+fn validators(headers: &HeaderMap) -> Validators {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+    };
+    Validators {
+        etag: header(ETAG),
+        last_modified: header(LAST_MODIFIED),
+    }
+}
+
+/// Refuse responses that can't hold headlines, such as PDFs, images, JSON
+/// APIs or plain text, which would otherwise parse as pages of garbage. Only
+/// HTML, XHTML and XML types (which cover RSS and Atom) are accepted, plus
+/// JSON Feed under `--strategy feed`; so is a missing header.
+fn check_content_type(
+    url: &str,
+    headers: &HeaderMap,
+    strategy: Strategy,
+) -> Result<(), ScraperError> {
+    let Some(content_type) = headers.get(CONTENT_TYPE) else {
+        return Ok(());
+    };
+    let content_type = String::from_utf8_lossy(content_type.as_bytes());
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let acceptable = matches!(
+        essence.as_str(),
+        "" | "text/html" | "application/xhtml+xml" | "text/xml" | "application/xml"
+    ) || essence.ends_with("+xml")
+        || (strategy == Strategy::Feed && essence == "application/feed+json");
+    if acceptable {
+        Ok(())
+    } else {
+        Err(ScraperError::UnexpectedContentType {
+            url: url.to_string(),
+            content_type: essence,
+        })
+    }
+}
+
+/// Stream the body, giving up as soon as it exceeds `limit` bytes, and
+/// decode it with [`decode_body`].
+async fn read_body(mut response: Response, url: &str, limit: u64) -> Result<String, ScraperError> {
+    let too_large = || ScraperError::BodyTooLarge {
+        url: url.to_string(),
+        limit,
+    };
+    // Fail fast when the server announces the size up front
+    if response
+        .content_length()
+        .is_some_and(|length| length > limit)
+    {
+        return Err(too_large());
+    }
+
+    let content_type = response
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| request_error(url, e))? {
+        if (bytes.len() + chunk.len()) as u64 > limit {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(decode_body(&bytes, content_type.as_deref()))
+}
+
+/// Read a saved page from a `file://` URL, decoding it like a downloaded one.
+pub fn read_local_page(url: &Url, limit: u64) -> Result<String, ScraperError> {
+    let path = url
+        .to_file_path()
+        .map_err(|_| ScraperError::InvalidUrl(format!("{}: not a local file path", url)))?;
+    let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", path.display(), e));
+    if fs::metadata(&path).map_err(with_path)?.len() > limit {
+        return Err(ScraperError::BodyTooLarge {
+            url: url.to_string(),
+            limit,
+        });
+    }
+    let bytes = fs::read(&path).map_err(with_path)?;
+    Ok(decode_body(&bytes, None))
+}
+
+/// Decode a page body, trusting a byte-order mark first, then the charset
+/// from `content_type`, then a `<meta>` charset near the start of the page.
+/// Declarations are often wrong, so one that the bytes contradict is
+/// skipped: UTF-8 that doesn't validate, or a legacy charset for text that
+/// is valid, non-ASCII UTF-8. With nothing usable, valid UTF-8 is taken as
+/// such and anything else as Windows-1252, the usual mislabelled charset.
+pub fn decode_body(bytes: &[u8], content_type: Option<&str>) -> String {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding.decode_with_bom_removal(bytes).0.into_owned();
+    }
+    let is_utf8 = std::str::from_utf8(bytes).is_ok();
+    let fits = |encoding: &&'static Encoding| {
+        if *encoding == UTF_8 {
+            is_utf8
+        } else {
+            !is_utf8 || bytes.is_ascii()
+        }
+    };
+    let declared = [content_type.and_then(header_charset), meta_charset(bytes)];
+    let encoding = match declared.into_iter().flatten().find(fits) {
+        Some(encoding) => encoding,
+        None if is_utf8 => UTF_8,
+        None => WINDOWS_1252,
+    };
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+/// The charset parameter of a `Content-Type` header.
+fn header_charset(content_type: &str) -> Option<&'static Encoding> {
+    content_type.split(';').find_map(|param| {
+        let (key, label) = param.trim().split_once('=')?;
+        key.eq_ignore_ascii_case("charset")
+            .then(|| Encoding::for_label(label.trim_matches('"').as_bytes()))
+            .flatten()
+    })
+}
+
+/// The charset declared by a `<meta charset>` or `<meta http-equiv>` tag in
+/// the first 1024 bytes, where browsers look for it.
+fn meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(1024)]).to_ascii_lowercase();
+    head.split("<meta").skip(1).find_map(|tag| {
+        let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+        let value = tag
+            .split_once("charset")?
+            .1
+            .trim_start()
+            .strip_prefix('=')?;
+        let label: String = value
+            .trim_start()
+            .trim_start_matches(['"', '\''])
+            .chars()
+            .take_while(|c| !matches!(c, '"' | '\'' | ';' | '/') && !c.is_ascii_whitespace())
+            .collect();
+        // A page can't really be UTF-16 if its markup reads as ASCII
+        Encoding::for_label(label.as_bytes()).map(Encoding::output_encoding)
+    })
+}
+
+/// Fail fast with [`ScraperError::CircuitOpen`] while `url`'s host is
+/// being skipped by the circuit breaker.
+fn check_circuit(options: &FetchOptions, url: &str) -> Result<(), ScraperError> {
+    match options
+        .circuit_breaker
+        .as_ref()
+        .and_then(|b| b.open_host(url))
+    {
+        Some(host) => Err(ScraperError::CircuitOpen(host)),
+        None => Ok(()),
+    }
+}
+
+/// Failures that suggest the host itself is down or refusing us, as opposed
+/// to a missing page.
+fn is_host_failure(error: &ScraperError) -> bool {
+    matches!(error, ScraperError::RequestError(_)) || is_retriable(error)
+}
+
+/// Only timeouts and server errors are worth retrying; anything else would
+/// fail the same way again.
+fn is_retriable(error: &ScraperError) -> bool {
+    match error {
+        ScraperError::Timeout(_) | ScraperError::ConnectTimeout(_) => true,
+        ScraperError::HttpStatus { code, .. } => *code >= 500,
+        _ => false,
+    }
+}
+
+/// Convert a reqwest error, keeping timeouts distinct from other network
+/// failures and connection timeouts distinct from slow responses.
+fn request_error(url: &str, error: reqwest::Error) -> ScraperError {
+    if error.is_connect() && error.is_timeout() {
+        ScraperError::ConnectTimeout(url.to_string())
+    } else if error.is_timeout() {
+        ScraperError::Timeout(url.to_string())
+    } else {
+        ScraperError::RequestError(error)
+    }
+}
@@ -0,0 +1,147 @@
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::ScraperError;
+
+/// Sentiment results persisted across runs in a SQLite database.
+pub struct History {
+    conn: Connection,
+}
+
+impl History {
+    /// Open (or create) the database at `path`, creating the table if needed.
+    pub fn open(path: &str) -> Result<Self, ScraperError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    fn init(conn: Connection) -> Result<Self, ScraperError> {
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS runs (
+                id INTEGER PRIMARY KEY,
+                started_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE TABLE IF NOT EXISTS headlines (
+                id INTEGER PRIMARY KEY,
+                source TEXT NOT NULL,
+                headline TEXT NOT NULL,
+                sentiment REAL NOT NULL,
+                scraped_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE INDEX IF NOT EXISTS headlines_source_time
+                ON headlines (source, scraped_at);",
+        )?;
+        // Databases written before runs were tracked lack the column; their
+        // rows keep a NULL run and are left out of comparisons
+        let has_run_id: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('headlines') WHERE name = 'run_id'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_run_id {
+            conn.execute_batch(
+                "ALTER TABLE headlines ADD COLUMN run_id INTEGER REFERENCES runs (id)",
+            )?;
+        }
+        Ok(History { conn })
+    }
+
+    /// Record a new run with one row per result, all stamped with the current
+    /// UTC time.
+    pub fn record(&mut self, results: &[HashMap<String, Value>]) -> Result<(), ScraperError> {
+        let tx = self.conn.transaction()?;
+        tx.execute("INSERT INTO runs DEFAULT VALUES", [])?;
+        let run_id = tx.last_insert_rowid();
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO headlines (run_id, source, headline, sentiment)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for result in results {
+                insert.execute(params![
+                    run_id,
+                    result["source"].as_str().unwrap_or_default(),
+                    result["headline"].as_str().unwrap_or_default(),
+                    result["sentiment"].as_f64().unwrap(),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Average sentiment for `source` in the most recent recorded run that
+    /// included it.
+    pub fn previous_average(&self, source: &str) -> Result<Option<f64>, ScraperError> {
+        let average = self.conn.query_row(
+            "SELECT AVG(sentiment) FROM headlines
+             WHERE run_id = (SELECT MAX(run_id) FROM headlines WHERE source = ?1)
+               AND source = ?1",
+            params![source],
+            |row| row.get(0),
+        )?;
+        Ok(average)
+    }
+
+    /// Average sentiment per UTC day for `source` as `(day, headlines, average)`,
+    /// most recent first.
+    pub fn daily_averages(
+        &self,
+        source: &str,
+        days: usize,
+    ) -> Result<Vec<(String, usize, f64)>, ScraperError> {
+        let mut query = self.conn.prepare(
+            "SELECT date(scraped_at), COUNT(*), AVG(sentiment) FROM headlines
+             WHERE source = ?1 GROUP BY 1 ORDER BY 1 DESC LIMIT ?2",
+        )?;
+        let rows = query.query_map(params![source, days as i64], |row| {
+            Ok((row.get(0)?, row.get::<_, i64>(1)? as usize, row.get(2)?))
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(source: &str, headline: &str, sentiment: f64) -> HashMap<String, Value> {
+        HashMap::from([
+            ("source".to_string(), Value::from(source)),
+            ("headline".to_string(), Value::from(headline)),
+            ("sentiment".to_string(), Value::from(sentiment)),
+        ])
+    }
+
+    #[test]
+    fn averages_recorded_results_per_source() {
+        let mut history = History::init(Connection::open_in_memory().unwrap()).unwrap();
+        history
+            .record(&[
+                result("a", "Good news today", 0.5),
+                result("a", "Bad news today", -0.1),
+                result("b", "Other news today", 0.9),
+            ])
+            .unwrap();
+        let days = history.daily_averages("a", 30).unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].1, 2);
+        assert!((days[0].2 - 0.2).abs() < 1e-9);
+    }
+
+    #[test]
+    fn previous_average_uses_latest_run_with_the_source() {
+        let mut history = History::init(Connection::open_in_memory().unwrap()).unwrap();
+        assert_eq!(history.previous_average("a").unwrap(), None);
+        history
+            .record(&[result("a", "First run here", 0.4)])
+            .unwrap();
+        history
+            .record(&[result("a", "Second run here", -0.2)])
+            .unwrap();
+        history
+            .record(&[result("b", "Unrelated run here", 0.9)])
+            .unwrap();
+        assert_eq!(history.previous_average("a").unwrap(), Some(-0.2));
+    }
+}
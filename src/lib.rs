@@ -0,0 +1,355 @@
+//! Scrape news headlines and score their sentiment with VADER.
+//!
+//! ```no_run
+//! use reqwest::Client;
+//! use scraper_cli::{Config, FetchOptions, Scraper};
+//!
+//! # async fn run() -> Result<(), scraper_cli::ScraperError> {
+//! let scraper = Scraper::new(Client::new(), Config::default(), FetchOptions::default());
+//! let headlines = scraper.fetch("https://www.bbc.com").await?;
+//! for result in scraper.analyze("https://www.bbc.com", &headlines) {
+//!     println!("{:.2} {}", result.sentiment, result.headline);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod cache;
+pub mod circuit_breaker;
+pub mod config;
+pub mod external;
+pub mod extract;
+pub mod fetch;
+pub mod history;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod output;
+pub mod random;
+pub mod rate_limit;
+pub mod robots;
+pub mod sentiment;
+
+use reqwest::{Client, Url};
+use scraper::Selector;
+use thiserror::Error;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+pub use config::Config;
+use extract::{
+    dedupe_headlines, extract_feed_headlines, extract_headlines, extract_jsonld_headlines,
+    extract_lines,
+};
+pub use extract::{Headline, Strategy};
+pub use fetch::FetchOptions;
+use fetch::{fetch_page, read_local_page, refetch_page};
+pub use sentiment::SentimentResult;
+use sentiment::{analyze_headlines, Scorer, Vader, DEFAULT_NEUTRAL_BAND};
+
+#[derive(Debug, Error)]
+#[allow(clippy::enum_variant_names)]
+pub enum ScraperError {
+    #[error("Network request error: {0}")]
+    RequestError(#[from] reqwest::Error),
+    #[error("Request to {0} timed out")]
+    Timeout(String),
+    #[error("Could not connect to {0} within the --connect-timeout")]
+    ConnectTimeout(String),
+    #[error("{url} returned HTTP status {code}")]
+    HttpStatus { url: String, code: u16 },
+    #[error("Invalid URL {0} (expected an address such as https://www.bbc.com)")]
+    InvalidUrl(String),
+    #[error("Circuit open for {0} after repeated failures; skipping it for the rest of the run")]
+    CircuitOpen(String),
+    #[error("robots.txt disallows scraping {0} (use --ignore-robots to override)")]
+    DisallowedByRobots(String),
+    #[error("No selector configured for {0} (add one with --config or --selector)")]
+    UnsupportedDomain(String),
+    #[error("{0} returned an empty body")]
+    EmptyBody(String),
+    #[error("{url} yielded {found} headlines, fewer than --min-headlines {min}")]
+    TooFewHeadlines {
+        url: String,
+        found: usize,
+        min: usize,
+    },
+    #[error("{0} cannot be combined with --format ndjson")]
+    UnsupportedWithStreaming(String),
+    #[error("Failed to parse feed {0}")]
+    FeedError(String),
+    #[error("{url} returned {content_type}, not HTML (use --force-html to parse it anyway)")]
+    UnexpectedContentType { url: String, content_type: String },
+    #[error("{url} is larger than the --max-body-size limit of {limit} bytes")]
+    BodyTooLarge { url: String, limit: u64 },
+    #[error("Invalid CSS selector \"{0}\"")]
+    InvalidSelector(String),
+    #[error("Invalid proxy {0}")]
+    ProxyError(String),
+    #[error("Failed to load CA certificate {0}")]
+    CertificateError(String),
+    #[error("Invalid config file {0}")]
+    ConfigError(String),
+    #[error("Invalid lexicon {0}")]
+    LexiconError(String),
+    #[error("Extractor command {0}")]
+    ExtractorError(String),
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("CSV write error: {0}")]
+    CsvError(#[from] csv::Error),
+    #[error("History database error: {0}")]
+    DatabaseError(#[from] rusqlite::Error),
+}
+
+impl ScraperError {
+    /// A short category for the end-of-run error summary, e.g. `timeout`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScraperError::Timeout(_) | ScraperError::ConnectTimeout(_) => "timeout",
+            ScraperError::HttpStatus { .. } => "http status",
+            ScraperError::RequestError(_)
+            | ScraperError::ProxyError(_)
+            | ScraperError::CertificateError(_) => "network",
+            ScraperError::EmptyBody(_) | ScraperError::TooFewHeadlines { .. } => "empty",
+            ScraperError::FeedError(_) | ScraperError::ExtractorError(_) => "parse",
+            ScraperError::UnexpectedContentType { .. } => "content type",
+            ScraperError::BodyTooLarge { .. } => "too large",
+            ScraperError::InvalidUrl(_) => "invalid url",
+            ScraperError::CircuitOpen(_) | ScraperError::DisallowedByRobots(_) => "skipped",
+            ScraperError::UnsupportedDomain(_)
+            | ScraperError::InvalidSelector(_)
+            | ScraperError::ConfigError(_)
+            | ScraperError::LexiconError(_)
+            | ScraperError::UnsupportedWithStreaming(_) => "config",
+            ScraperError::IoError(_)
+            | ScraperError::CsvError(_)
+            | ScraperError::DatabaseError(_) => "io",
+        }
+    }
+}
+
+/// A desktop browser User-Agent; several built-in sources block reqwest's default one.
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/124.0.0.0 Safari/537.36";
+
+/// Parse `url`, accepting only http and https unless `allow_local` is set.
+pub fn validate_url(url: &str, allow_local: bool) -> Result<Url, ScraperError> {
+    let parsed =
+        Url::parse(url).map_err(|e| ScraperError::InvalidUrl(format!("{}: {}", url, e)))?;
+    if !allow_local && !matches!(parsed.scheme(), "http" | "https") {
+        return Err(ScraperError::InvalidUrl(format!(
+            "{}: unsupported scheme {} (use --allow-local to permit it)",
+            url,
+            parsed.scheme()
+        )));
+    }
+    Ok(parsed)
+}
+
+/// Fetches headlines with a shared HTTP client, source config and fetch
+/// options, and scores them.
+pub struct Scraper {
+    client: Client,
+    config: Config,
+    options: FetchOptions,
+    scorer: Box<dyn Scorer>,
+    neutral_band: f64,
+}
+
+impl Scraper {
+    pub fn new(client: Client, config: Config, options: FetchOptions) -> Self {
+        Scraper {
+            client,
+            config,
+            options,
+            scorer: Box::new(Vader::default()),
+            neutral_band: DEFAULT_NEUTRAL_BAND,
+        }
+    }
+
+    /// Label compound scores within `band` of zero as neutral.
+    pub fn with_neutral_band(mut self, band: f64) -> Self {
+        self.neutral_band = band;
+        self
+    }
+
+    /// Rate headlines with `scorer` instead of VADER.
+    pub fn with_scorer(mut self, scorer: impl Scorer + 'static) -> Self {
+        self.scorer = Box::new(scorer);
+        self
+    }
+
+    pub fn scorer(&self) -> &dyn Scorer {
+        self.scorer.as_ref()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn options(&self) -> &FetchOptions {
+        &self.options
+    }
+
+    /// Fetch `url` and extract its headlines.
+    pub async fn fetch(&self, url: &str) -> Result<Vec<Headline>, ScraperError> {
+        let result = fetch_website_data(&self.client, &self.options, &self.config, url).await;
+        #[cfg(feature = "metrics")]
+        if let Some(metrics) = &self.options.metrics {
+            let headlines = result.as_ref().ok().map(|headlines| headlines.len());
+            metrics.record_source(url, headlines);
+        }
+        result
+    }
+
+    /// Score headlines scraped from `source`.
+    pub fn analyze(&self, source: &str, headlines: &[Headline]) -> Vec<SentimentResult> {
+        analyze_headlines(source, headlines, self.scorer(), self.neutral_band)
+    }
+}
+
+/// Fetch `url` and extract its headlines with the rules `config` has for its
+/// domain.
+#[tracing::instrument(skip(client, options, config))]
+pub async fn fetch_website_data(
+    client: &Client,
+    options: &FetchOptions,
+    config: &Config,
+    url: &str,
+) -> Result<Vec<Headline>, ScraperError> {
+    let base_url = validate_url(url, options.allow_local)?;
+    // Determine which website to scrape from based on the URL, before
+    // spending a request on a page we cannot parse. JSON-LD needs no
+    // per-site rules, so any URL will do, and neither does an external
+    // extractor.
+    let source = config.source_for(url);
+    let parse = |selector: &String| {
+        Selector::parse(selector)
+            .map(|parsed| (parsed, source.and_then(|s| s.attribute.clone())))
+            .map_err(|_| ScraperError::InvalidSelector(selector.clone()))
+    };
+    // The merged primary selectors, then each fallback on its own, as
+    // `(description, selectors)` tried in order until one matches
+    let mut chain = Vec::new();
+    if options.strategy == Strategy::Css && options.extractor.is_none() {
+        let source = source.ok_or_else(|| ScraperError::UnsupportedDomain(url.to_string()))?;
+        chain.push((
+            source.selector.join(", "),
+            source
+                .selector
+                .iter()
+                .map(parse)
+                .collect::<Result<Vec<_>, _>>()?,
+        ));
+        for fallback in &source.fallback_selectors {
+            chain.push((fallback.clone(), vec![parse(fallback)?]));
+        }
+    }
+
+    // Saved pages are read straight from disk, with no network involved
+    let local = base_url.scheme() == "file";
+    let text = if local {
+        read_local_page(&base_url, options.max_body_size)?
+    } else {
+        fetch_page(client, options, url).await?
+    };
+    if text.trim().is_empty() {
+        return Err(ScraperError::EmptyBody(url.to_string()));
+    }
+
+    let mut filter = options.filter.clone();
+    if let Some(source) = source {
+        filter
+            .unwanted
+            .extend(source.unwanted_headlines.iter().cloned());
+    }
+    let extract_page = |text: &str| -> Result<(Vec<Headline>, &str), ScraperError> {
+        match options.strategy {
+            Strategy::Css => {
+                for (position, (description, selectors)) in chain.iter().enumerate() {
+                    let headlines = extract_headlines(text, selectors, &filter, Some(&base_url));
+                    if !headlines.is_empty() {
+                        if position > 0 {
+                            info!(selector = %description, "primary selector matched nothing; using fallback");
+                        }
+                        return Ok((headlines, description));
+                    }
+                }
+                Ok((Vec::new(), &chain[0].0))
+            }
+            Strategy::Jsonld => Ok((
+                extract_jsonld_headlines(text, &filter, Some(&base_url)),
+                "JSON-LD",
+            )),
+            Strategy::Feed => extract_feed_headlines(text, &filter, Some(&base_url))
+                .map(|headlines| (headlines, "feed"))
+                .map_err(|e| ScraperError::FeedError(format!("{}: {}", url, e))),
+        }
+    };
+    let (extract_page, filter) = (&extract_page, &filter);
+    let extract = |text: String| async move {
+        match &options.extractor {
+            Some(extractor) => Ok((
+                extract_lines(&extractor.run(&text, url).await?, filter),
+                extractor.command(),
+            )),
+            None => extract_page(&text),
+        }
+    };
+    let (mut headlines, mut matched) = extract(text).await?;
+
+    let mut attempt = 0;
+    while options.retry_on_empty && !local && headlines.is_empty() && attempt < options.retries {
+        let delay = options.backoff(attempt);
+        attempt += 1;
+        warn!(
+            "No headlines matched; re-fetching in {}ms (attempt {}/{})",
+            delay.as_millis(),
+            attempt,
+            options.retries
+        );
+        sleep(delay).await;
+        (headlines, matched) = extract(refetch_page(client, options, url).await?).await?;
+        if !headlines.is_empty() {
+            warn!(
+                "Re-fetch found {} headlines after {} empty result(s)",
+                headlines.len(),
+                attempt
+            );
+        }
+    }
+
+    // Sites repeat headlines across page sections, which would skew the average
+    if options.dedupe {
+        headlines = dedupe_headlines(headlines, options.dedupe_ignore_case);
+    }
+    if let Some(min) = options.min_headlines {
+        if headlines.len() < min {
+            return Err(ScraperError::TooFewHeadlines {
+                url: url.to_string(),
+                found: headlines.len(),
+                min,
+            });
+        }
+    }
+    if let Some(limit) = options.limit {
+        headlines.truncate(limit);
+    }
+    if let Some(sample) = options.sample.filter(|&sample| sample < headlines.len()) {
+        let picked = options
+            .rng
+            .with(|rng| rand::seq::index::sample(rng, headlines.len(), sample));
+        let mut keep = vec![false; headlines.len()];
+        for i in picked {
+            keep[i] = true;
+        }
+        // Sampled headlines keep their scrape order
+        let mut keep = keep.into_iter();
+        headlines.retain(|_| keep.next().unwrap_or(false));
+    }
+    info!(
+        selector = %matched,
+        headlines = headlines.len(),
+        "extracted headlines"
+    );
+    Ok(headlines)
+}
@@ -1,201 +1,1597 @@
-use clap::{ArgGroup, Parser};
-use reqwest;
-use scraper::{Html, Selector};
+#[cfg(test)]
+mod tests;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use clap::builder::ArgPredicate;
+use clap::{ArgAction, ArgGroup, Parser, ValueEnum};
+use futures::stream::{self, Stream, StreamExt};
+use indicatif::{ProgressBar, ProgressStyle};
+use rand::seq::SliceRandom;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT_LANGUAGE, AUTHORIZATION};
+use reqwest::{redirect, Certificate, Client, Proxy, Url};
+use scraper::Selector;
+use scraper_cli::cache::Cache;
+use scraper_cli::circuit_breaker::CircuitBreaker;
+use scraper_cli::config::{parse_line_list, Config, SourceConfig};
+use scraper_cli::external::ExternalExtractor;
+use scraper_cli::extract::{Headline, HeadlineFilter, Strategy};
+use scraper_cli::fetch::{FetchOptions, FixedCookies, Jitter, UserAgentPool};
+use scraper_cli::history::History;
+use scraper_cli::output::{
+    append_output, open_output, print_comparison, print_error_summary, print_headline_counts,
+    print_history, print_jsonl_headlines, print_jsonl_summary, print_phrase_extremes,
+    print_sentiment_results, print_side_by_side, print_sparkline, print_token_frequencies,
+    source_averages, HeadlineCase, OutputFormat, OutputOptions,
+};
+use scraper_cli::random::SharedRng;
+use scraper_cli::rate_limit::RateLimiter;
+use scraper_cli::robots::RobotsCache;
+use scraper_cli::sentiment::{
+    ngram_scores, sentiment_label, token_frequencies, Lexicon, SentimentModel, SentimentResult,
+    DEFAULT_NEUTRAL_BAND,
+};
+use scraper_cli::{validate_url, Scraper, ScraperError, DEFAULT_USER_AGENT};
 use serde_json::Value;
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use thiserror::Error;
-use vader_sentiment::SentimentIntensityAnalyzer;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::signal;
+use tokio::sync::watch;
+use tokio::time::sleep;
+use tracing::{error, warn};
+use tracing_subscriber::EnvFilter;
 
 #[derive(Parser)]
 #[command(name = "Scraper CLI")]
 #[command(about = "A simple web scraper for extracting headlines and performing sentiment analysis", long_about = None)]
-#[command(group(ArgGroup::new("mode").required(true).args(&["url", "all"])))]
+#[command(after_help = "Exit codes:
+  0  headlines were found and analyzed
+  1  the run failed (or every source failed)
+  2  no headlines were found
+  3  some sources failed while others succeeded
+  130  interrupted by Ctrl-C after reporting partial results
+
+Environment:
+  SCRAPER_TIMEOUT, SCRAPER_USER_AGENT, SCRAPER_CONCURRENCY, SCRAPER_FORMAT and
+  SCRAPER_OUTPUT set defaults for the matching flags; a flag given on the
+  command line always overrides its variable. SCRAPER_BASIC_AUTH and
+  SCRAPER_BEARER keep credentials out of the process list
+
+Cookies:
+  Sites behind a consent banner (e.g. the Guardian, or the BBC in the EU) may
+  return no headlines until consent is given. Accept the banner in a browser,
+  copy the consent cookie from its developer tools and pass it with --cookie,
+  e.g. --cookie ckns_policy=111 for the BBC")]
+#[command(group(ArgGroup::new("mode").required(true).args(&["url", "all", "urls_file", "history", "sparkline", "compare_urls"])))]
+#[command(group(ArgGroup::new("auth").args(&["basic_auth", "bearer"])))]
 struct Args {
-    /// URL to scrape
-    #[arg(short, long, group = "mode")]
-    url: Option<String>,
+    /// URL to scrape, or `-` to read a list of URLs from stdin; repeat the flag
+    /// or list several URLs to aggregate them like `--all`. With --allow-local,
+    /// a saved page's path or file:// URL is read from disk instead
+    #[arg(short, long, group = "mode", num_args = 1..)]
+    url: Vec<String>,
 
     /// Analyze all sources
     #[arg(short, long, group = "mode")]
     all: bool,
+
+    /// Fetch the sources in a random order (reproducible with --seed) instead
+    /// of the order they are listed in, so repeated runs don't hit them in
+    /// the same sequence
+    #[arg(long)]
+    shuffle_sources: bool,
+
+    /// File of newline-delimited URLs to scrape; blank lines and `#` comments are skipped
+    #[arg(long, group = "mode")]
+    urls_file: Option<String>,
+
+    /// Print recent daily average sentiment for this source from the `--db` history
+    #[arg(long, group = "mode", requires = "db")]
+    history: Option<String>,
+
+    /// Draw this source's recent daily average sentiment from the `--db`
+    /// history as a one-line sparkline, oldest day first. Pass the URL exactly
+    /// as it was scraped, e.g. https://www.bbc.com
+    #[arg(long, value_name = "URL", group = "mode", requires = "db")]
+    sparkline: Option<String>,
+
+    /// Scrape two URLs and compare their headline counts and average sentiment
+    /// side by side (`--compare` diffs against the `--db` history instead)
+    #[arg(
+        long,
+        group = "mode",
+        num_args = 2,
+        value_names = ["URL1", "URL2"],
+        conflicts_with_all = ["count_only", "db"]
+    )]
+    compare_urls: Vec<String>,
+
+    /// Output format
+    #[arg(short, long, value_enum, env = "SCRAPER_FORMAT", default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// HTTP request timeout in seconds
+    #[arg(long, env = "SCRAPER_TIMEOUT", default_value_t = 30)]
+    timeout: u64,
+
+    /// Give up on hosts that don't accept a connection within this many
+    /// seconds, however long --timeout allows for the whole request
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    connect_timeout: Option<u64>,
+
+    /// User-Agent header sent with every request
+    #[arg(long, env = "SCRAPER_USER_AGENT", default_value = DEFAULT_USER_AGENT)]
+    user_agent: String,
+
+    /// Cycle through a built-in pool of browser User-Agents, one per request
+    /// (led by --user-agent when it is set to something else)
+    #[arg(long)]
+    user_agent_rotate: bool,
+
+    /// Accept-Language header sent with every request; sites pick their
+    /// regional edition, and so their markup, from it
+    #[arg(long, default_value = "en-US,en", value_parser = parse_header_value)]
+    accept_language: HeaderValue,
+
+    /// Send this `name=value` cookie to every site, e.g. to get past a consent
+    /// wall (repeatable)
+    #[arg(long, value_name = "NAME=VALUE", value_parser = parse_cookie)]
+    cookie: Vec<String>,
+
+    /// Send this `key=value` header with every request, overriding the built-in
+    /// ones such as User-Agent (repeatable)
+    #[arg(long, value_name = "KEY=VALUE", value_parser = parse_header)]
+    header: Vec<(HeaderName, HeaderValue)>,
+
+    /// Log in to every site with HTTP basic authentication
+    #[arg(long, env = "SCRAPER_BASIC_AUTH", value_name = "USER:PASS", value_parser = parse_basic_auth, hide_env_values = true)]
+    basic_auth: Option<HeaderValue>,
+
+    /// Send this bearer token to every site
+    #[arg(long, env = "SCRAPER_BEARER", value_name = "TOKEN", value_parser = parse_bearer, hide_env_values = true)]
+    bearer: Option<HeaderValue>,
+
+    /// TOML file defining sources and selectors (defaults to the built-in list)
+    #[arg(short, long)]
+    config: Option<String>,
+
+    /// Number of times to retry a timed-out or 5xx request
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+
+    /// Delay in milliseconds before the first retry, doubled after each attempt
+    #[arg(long, default_value_t = 500)]
+    retry_delay: u64,
+
+    /// Randomize retry delays so concurrent fetches of a failing host don't
+    /// retry in lockstep
+    #[arg(long, value_enum, default_value_t = Jitter::Equal)]
+    retry_jitter: Jitter,
+
+    /// Re-fetch a page (up to --retries times) when no headlines match, e.g. after
+    /// a bot-detection page or lazily loaded content
+    #[arg(long)]
+    retry_on_empty: bool,
+
+    /// Abort downloads larger than this many bytes
+    #[arg(long, default_value_t = 10 * 1024 * 1024)]
+    max_body_size: u64,
+
+    /// Accept URLs with schemes other than http and https, such as file://
+    /// for pages saved to disk
+    #[arg(long)]
+    allow_local: bool,
+
+    /// Parse responses whatever their Content-Type, for servers that mislabel
+    /// HTML. Otherwise only HTML, XHTML and XML types (including RSS and
+    /// Atom) are parsed, plus application/feed+json with --strategy feed
+    #[arg(long)]
+    force_html: bool,
+
+    /// Scrape even when the site's robots.txt disallows it
+    #[arg(long)]
+    ignore_robots: bool,
+
+    /// Write results to this file instead of stdout
+    #[arg(short, long, env = "SCRAPER_OUTPUT")]
+    output: Option<String>,
+
+    /// Write each source's results to its own file in this directory, named
+    /// after its domain (e.g. `bbc.com.json`), instead of to stdout
+    #[arg(long, conflicts_with = "output")]
+    output_dir: Option<String>,
+
+    /// Count sources that yielded no headlines as neutral in the overall
+    /// sentiment; by default they are listed with 0 headlines but left out
+    /// of the average
+    #[arg(long)]
+    include_empty_sources: bool,
+
+    /// Add results to the end of the --output file instead of replacing it,
+    /// to build up a dataset over scheduled runs; CSV and TSV headers are
+    /// only written to a new file. Best with --format jsonl
+    #[arg(long, requires = "output")]
+    output_append: bool,
+
+    /// Show per-headline score breakdowns and log progress to stderr
+    /// (-v info, -vv debug, -vvv trace)
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Append a histogram of compound scores split into this many equal-width bins
+    /// to text output
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..=100))]
+    bins: Option<u32>,
+
+    /// Also score every N-word phrase in the headlines and list the most
+    /// positive and negative ones (to stderr unless --format is text)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..=10))]
+    phrase_ngrams: Option<u32>,
+
+    /// Also list the N most common words across all headlines, leaving out
+    /// stopwords, with the average sentiment of the headlines using each (to
+    /// stderr unless --format is text)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    tokens: Option<u32>,
+
+    /// Also report the overall sentiment weighted by each headline's word count
+    #[arg(long)]
+    weight_by_length: bool,
+
+    /// Also report the median and standard deviation of compound scores, which
+    /// describe skewed days better than the mean alone
+    #[arg(long)]
+    stats: bool,
+
+    /// Change the casing of headlines in the output. Sentiment is still scored
+    /// on the original text, since VADER reads ALL-CAPS words as emphasis.
+    #[arg(long, value_enum, default_value_t = HeadlineCase::AsIs)]
+    case: HeadlineCase,
+
+    /// Print only the aggregate sentiment (and per-source averages), not each
+    /// headline, and hide the progress bar
+    #[arg(long, visible_alias = "quiet", conflicts_with = "verbose")]
+    summary: bool,
+
+    /// Never color text output (also disabled by a non-empty NO_COLOR, or when
+    /// stdout is not a terminal)
+    #[arg(long)]
+    no_color: bool,
+
+    /// Drop headlines with fewer words than this
+    #[arg(long, default_value_t = 2)]
+    min_words: usize,
+
+    /// Drop headlines with more words than this, e.g. matched article paragraphs
+    #[arg(long)]
+    max_words: Option<usize>,
+
+    /// Keep only headlines containing this term (case-insensitive, repeatable)
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// Drop headlines containing this term (case-insensitive, repeatable)
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// File of exact headline titles to drop, one per line, added to the
+    /// built-in (or configured) unwanted list
+    #[arg(long)]
+    exclude_file: Option<String>,
+
+    /// Serve Prometheus metrics on this port of --metrics-addr (localhost by
+    /// default) during the run, then keep serving them until Ctrl-C so they
+    /// can be scraped
+    #[cfg(feature = "metrics")]
+    #[arg(long, value_name = "PORT")]
+    metrics_port: Option<u16>,
+
+    /// Address the --metrics-port endpoint listens on; it has no
+    /// authentication, so use 0.0.0.0 only to expose it deliberately
+    #[cfg(feature = "metrics")]
+    #[arg(
+        long,
+        value_name = "IP",
+        default_value = "127.0.0.1",
+        requires = "metrics_port"
+    )]
+    metrics_addr: std::net::IpAddr,
+
+    /// Push the run's Prometheus metrics to this Pushgateway when it finishes,
+    /// e.g. http://localhost:9091
+    #[cfg(feature = "metrics")]
+    #[arg(long, value_name = "URL")]
+    metrics_pushgateway: Option<String>,
+
+    /// Keep every non-empty text the selectors match, skipping the word-count,
+    /// keyword and unwanted-headline filters, to see what a selector captures
+    #[arg(long, conflicts_with_all = ["min_words", "max_words", "include", "exclude", "exclude_file"])]
+    no_filter: bool,
+
+    /// How headlines are found: CSS selectors, or JSON-LD article metadata
+    #[arg(long, value_enum, default_value_t = Strategy::Css)]
+    strategy: Strategy,
+
+    /// Treat every URL as an RSS/Atom feed; shorthand for `--strategy feed`
+    #[arg(long, conflicts_with = "strategy")]
+    feed: bool,
+
+    /// Extract headlines with an external program instead, for pages that
+    /// need JavaScript rendering: the command is run through the shell with
+    /// the page on stdin and SCRAPER_URL set, and prints one headline per line
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["strategy", "feed"])]
+    extractor_cmd: Option<String>,
+
+    /// Seconds the --extractor-cmd program may run on each page
+    #[arg(long, default_value_t = 30, requires = "extractor_cmd")]
+    extractor_timeout: u64,
+
+    /// CSS selector for headlines, overriding the built-in rules for every URL;
+    /// repeat to merge matches from several selectors
+    #[arg(short, long)]
+    selector: Vec<String>,
+
+    /// Read each headline from this attribute of the selected element
+    #[arg(long, requires = "selector")]
+    attribute: Option<String>,
+
+    /// How --selector matches are read: `text` (the default), `href`, or
+    /// `attr:NAME` for any other attribute, e.g. `attr:aria-label`
+    #[arg(long, value_name = "text|href|attr:NAME", value_parser = parse_extract, requires = "selector", conflicts_with = "attribute")]
+    extract: Option<Extraction>,
+
+    /// Leave HTML entities such as `&amp;` in headlines undecoded
+    #[arg(long)]
+    no_decode: bool,
+
+    /// Log every candidate dropped by the word-count, keyword or unwanted-headline
+    /// filters, with the reason, to stderr
+    #[arg(long)]
+    show_filtered: bool,
+
+    /// Keep repeated headlines instead of dropping duplicates
+    #[arg(long)]
+    keep_duplicates: bool,
+
+    /// Treat headlines differing only in case as duplicates
+    #[arg(long, conflicts_with = "keep_duplicates")]
+    ignore_case: bool,
+
+    /// Keep only the first N headlines from each source (0 means no limit)
+    #[arg(short, long)]
+    limit: Option<usize>,
+
+    /// Analyze a random sample of N headlines from each source, drawn with
+    /// --seed. With --limit, the sample is drawn from the first --limit headlines.
+    #[arg(long, value_name = "N")]
+    sample: Option<usize>,
+
+    /// Fail any source that yields fewer than N headlines, e.g. after its markup
+    /// changed and the selector stopped matching
+    #[arg(long, value_name = "N")]
+    min_headlines: Option<usize>,
+
+    /// Only report headlines with a compound sentiment of at least this (-1.0 to 1.0)
+    #[arg(long, value_parser = parse_compound, allow_negative_numbers = true)]
+    min_sentiment: Option<f64>,
+
+    /// Only report headlines with a compound sentiment of at most this (-1.0 to 1.0).
+    /// The overall average covers only the headlines inside the band.
+    #[arg(long, value_parser = parse_compound, allow_negative_numbers = true)]
+    max_sentiment: Option<f64>,
+
+    /// Report every compound score minus this value (score - baseline), so
+    /// aggregates show positivity relative to it; labels follow the shifted
+    /// scores, while --min/--max-sentiment still apply to the raw ones
+    #[arg(long, value_parser = parse_compound, allow_negative_numbers = true, conflicts_with = "db")]
+    baseline: Option<f64>,
+
+    /// Like --baseline, but subtract each source's own mean score, so every
+    /// source averages zero and headlines read as above or below its norm
+    #[arg(long, conflicts_with_all = ["baseline", "db"])]
+    normalize_baseline: bool,
+
+    /// How headlines are scored (defaults to `lexicon` when --lexicon is given)
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SentimentModel::Vader,
+        default_value_if("lexicon", ArgPredicate::IsPresent, "lexicon")
+    )]
+    sentiment_model: SentimentModel,
+
+    /// Word-to-score lexicon for `--sentiment-model lexicon`, one `word score`
+    /// pair per line as in VADER's own lexicon file, with scores from -4 to 4
+    #[arg(long, required_if_eq("sentiment_model", "lexicon"))]
+    lexicon: Option<String>,
+
+    /// Compound scores within ±this of zero are labelled neutral (0.0 to 1.0)
+    #[arg(long, default_value_t = DEFAULT_NEUTRAL_BAND, value_parser = parse_neutral_band)]
+    neutral_band: f64,
+
+    /// Order results by compound sentiment
+    #[arg(long, value_enum, default_value_t = SortOrder::None)]
+    sort: SortOrder,
+
+    /// Keep only the N most positive headlines across all sources. Applied after
+    /// --limit and the sentiment filters; --sort then orders what is left.
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Keep only the N most negative headlines, like --top; given together they
+    /// keep both ends
+    #[arg(long, value_name = "N")]
+    bottom: Option<usize>,
+
+    /// Skip headlines detected as non-English, which VADER cannot score meaningfully
+    #[arg(long)]
+    lang_filter: bool,
+
+    /// SQLite database recording every analyzed headline with a UTC timestamp
+    #[arg(long)]
+    db: Option<String>,
+
+    /// After analyzing, show how each source's average moved since the last run in `--db`
+    #[arg(long, requires = "db")]
+    compare: bool,
+
+    /// Validate selectors and URLs, then exit without making any requests
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Only report how many headlines each source matched, skipping sentiment analysis
+    #[arg(long)]
+    count_only: bool,
+
+    /// Send all requests through this HTTP/HTTPS proxy
+    /// (defaults to the HTTP_PROXY/HTTPS_PROXY environment variables)
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Maximum number of redirects to follow; 0 treats any redirect as an error
+    #[arg(long, default_value_t = 10)]
+    max_redirects: usize,
+
+    /// PEM file with an extra CA certificate to trust, e.g. for TLS-intercepting proxies
+    #[arg(long)]
+    ca_cert: Option<String>,
+
+    /// DANGEROUS: accept invalid or self-signed TLS certificates. For quick testing only.
+    #[arg(long)]
+    danger_accept_invalid_certs: bool,
+
+    /// Maximum number of sources fetched at once. Higher is faster but more
+    /// likely to get rate-limited or blocked by the target sites.
+    #[arg(long, env = "SCRAPER_CONCURRENCY", default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    concurrency: u32,
+
+    /// Abort a multi-source run at the first source that fails instead of
+    /// reporting the sources that worked
+    #[arg(long, overrides_with = "continue_on_error")]
+    fail_fast: bool,
+
+    /// Report the sources that worked and list failures at the end (the default;
+    /// overrides an earlier --fail-fast)
+    #[arg(long, overrides_with = "fail_fast")]
+    continue_on_error: bool,
+
+    /// Seed for every random choice in the run, making it reproducible
+    /// (seeded from system entropy when absent)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Minimum delay in milliseconds between requests to the same host
+    #[arg(long, default_value_t = 0)]
+    delay: u64,
+
+    /// Stop requesting a host for the rest of the run after this many
+    /// consecutive failed requests (timeouts, 5xx or connection errors)
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    circuit_breaker: Option<u32>,
+
+    /// Cache raw page bodies in this directory to avoid re-downloading
+    #[arg(long)]
+    cache_dir: Option<String>,
+
+    /// Seconds a cached page stays fresh
+    #[arg(long, default_value_t = 3600, requires = "cache_dir")]
+    cache_ttl: u64,
+
+    /// Ignore cached pages and fetch fresh copies (which are then cached)
+    #[arg(long, requires = "cache_dir")]
+    no_cache: bool,
 }
 
-#[derive(Debug, Error)]
-enum ScraperError {
-    #[error("Network request error: {0}")]
-    RequestError(#[from] reqwest::Error),
-    #[error("Failed to parse response")]
-    ParseError,
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SortOrder {
+    /// Most negative first
+    Asc,
+    /// Most positive first
+    Desc,
+    /// Keep scrape order
+    None,
 }
 
-const SOURCES: &[&str] = &[
-    "https://www.nytimes.com",
-    "https://www.theguardian.com",
-    "https://www.bbc.com",
-    "https://www.nature.com",
-    "https://www.economist.com",
-    "https://news.google.com",
-];
+/// Number of most recent days shown by `--history` and `--sparkline`.
+const HISTORY_DAYS: usize = 30;
+
+/// Phrases listed at each end by `--phrase-ngrams`.
+const PHRASE_EXTREMES: usize = 5;
+
+/// How long in-flight fetches may run after Ctrl-C before they are abandoned.
+const INTERRUPT_GRACE: Duration = Duration::from_secs(2);
+
+/// No headlines survived fetching and filtering.
+const EXIT_NO_HEADLINES: u8 = 2;
+/// Some sources in a multi-URL run failed while others succeeded.
+const EXIT_PARTIAL_FAILURE: u8 = 3;
+/// The run was cut short by Ctrl-C; partial results were still reported.
+const EXIT_INTERRUPTED: u8 = 130;
 
 #[tokio::main]
-async fn main() -> Result<(), ScraperError> {
+async fn main() -> ExitCode {
     let args = Args::parse();
-
-    if args.all {
-        let mut all_headlines = Vec::new();
-        for source in SOURCES {
-            let headlines = fetch_website_data(source).await?;
-            all_headlines.extend(headlines.get("headlines").unwrap().clone());
+    init_logging(args.verbose);
+    match run(args).await {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
         }
-        let sentiment_results = perform_sentiment_analysis(&all_headlines)?;
-        print_sentiment_results(&sentiment_results);
-    } else if let Some(url) = args.url {
-        let headlines = fetch_website_data(&url).await?;
-        let headlines_list = headlines.get("headlines").unwrap();
-        let sentiment_results = perform_sentiment_analysis(headlines_list)?;
-        print_sentiment_results(&sentiment_results);
     }
+}
 
-    Ok(())
+async fn run(mut args: Args) -> Result<ExitCode, ScraperError> {
+    args.url = args.url.iter().map(|url| local_path_url(url)).collect();
+    args.compare_urls = args
+        .compare_urls
+        .iter()
+        .map(|url| local_path_url(url))
+        .collect();
+    let client = build_client(&args)?;
+    let mut config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let mut options = fetch_options(&args);
+    options.filter.unwanted = config.unwanted_headlines.clone();
+    if let Some(path) = &args.exclude_file {
+        options
+            .filter
+            .unwanted
+            .extend(parse_line_list(&fs::read_to_string(path)?));
+    }
+    if !args.selector.is_empty() {
+        // Fail before fetching anything if a selector is malformed
+        for selector in &args.selector {
+            Selector::parse(selector)
+                .map_err(|_| ScraperError::InvalidSelector(selector.clone()))?;
+        }
+        // An empty domain matches every URL, so this rule takes precedence
+        config.selectors.insert(
+            0,
+            SourceConfig {
+                match_domain: String::new(),
+                selector: args.selector.clone(),
+                attribute: match &args.extract {
+                    Some(Extraction::Attribute(name)) => Some(name.clone()),
+                    Some(Extraction::Text) => None,
+                    None => args.attribute.clone(),
+                },
+                ..SourceConfig::default()
+            },
+        );
+    }
+    let mut scraper = Scraper::new(client, config, options).with_neutral_band(args.neutral_band);
+    if args.sentiment_model == SentimentModel::Lexicon {
+        // `required_if_eq` guarantees the path is present
+        scraper = scraper.with_scorer(Lexicon::load(args.lexicon.as_deref().unwrap_or_default())?);
+    }
+    #[cfg(feature = "metrics")]
+    return with_metrics(&args, &scraper, scrape(&args, &scraper)).await;
+    #[cfg(not(feature = "metrics"))]
+    scrape(&args, &scraper).await
 }
 
-async fn fetch_website_data(url: &str) -> Result<HashMap<String, Vec<String>>, ScraperError> {
-    let response = reqwest::get(url).await?;
-    let text = response.text().await?;
-
-    // Parse the HTML
-    let document = Html::parse_document(&text);
-
-    // Determine which website to scrape from based on the URL
-    let (headline_selector, attribute) = if url.contains("nytimes.com") {
-        (
-            Selector::parse("p.indicate-hover").map_err(|_| ScraperError::ParseError)?,
-            None,
-        )
-    } else if url.contains("theguardian.com") {
-        (
-            Selector::parse("a.dcr-lv2v9o").map_err(|_| ScraperError::ParseError)?,
-            Some("aria-label"),
-        )
-    } else if url.contains("bbc.com") {
-        (
-            Selector::parse("h2[data-testid='card-headline']")
-                .map_err(|_| ScraperError::ParseError)?,
-            None,
-        )
-    } else if url.contains("nature.com") {
-        (
-            Selector::parse("a.c-card__link").map_err(|_| ScraperError::ParseError)?,
-            None,
-        )
-    } else if url.contains("economist.com") {
-        (
-            Selector::parse("a[data-analytics]").map_err(|_| ScraperError::ParseError)?,
-            None,
-        )
-    } else if url.contains("news.google.com") {
-        (
-            Selector::parse("a.gPFEn").map_err(|_| ScraperError::ParseError)?,
-            None,
-        )
-    } else {
-        return Err(ScraperError::ParseError);
+/// Fetch, analyze and report according to `args`, once the scraper is set up.
+async fn scrape(args: &Args, scraper: &Scraper) -> Result<ExitCode, ScraperError> {
+    let (mut out, appending) = match args.output.as_deref() {
+        Some(path) if args.output_append => append_output(path)?,
+        path => (open_output(path)?, false),
+    };
+    let mut output_options = OutputOptions {
+        format: args.format,
+        verbose: args.verbose > 0,
+        summary: args.summary,
+        bins: args.bins.map(|bins| bins as usize),
+        weight_by_length: args.weight_by_length,
+        color: use_color(args),
+        stats: args.stats,
+        headers: !appending,
+        empty_sources: Vec::new(),
+        include_empty_sources: args.include_empty_sources,
     };
 
-    // Define a list of specific unwanted headlines
-    let unwanted_headlines = vec!["Connections Companion", "Spelling Bee", "The Crossword"];
-
-    // Extract the headlines
-    let headlines: Vec<String> = document
-        .select(&headline_selector)
-        .filter_map(|element| {
-            let text = match attribute {
-                Some(attr) => element.value().attr(attr).unwrap_or("").trim().to_string(),
-                None => element
-                    .text()
-                    .collect::<Vec<_>>()
-                    .join(" ")
-                    .trim()
-                    .to_string(),
-            };
-            if text.split_whitespace().count() > 1 && !unwanted_headlines.contains(&text.as_str()) {
-                // Filter out one-word and unwanted headlines
-                Some(text)
+    if let Some(source) = args.history.as_ref().or(args.sparkline.as_ref()) {
+        // `requires = "db"` guarantees the path is present
+        let history = History::open(args.db.as_deref().unwrap_or_default())?;
+        let days = history.daily_averages(source, HISTORY_DAYS)?;
+        if args.sparkline.is_some() {
+            print_sparkline(source, &days, &mut out)?;
+        } else {
+            print_history(&days, &mut out)?;
+        }
+        if days.is_empty() {
+            eprintln!("No history recorded for {}", source);
+            return Ok(ExitCode::from(EXIT_NO_HEADLINES));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.dry_run {
+        let urls = if args.compare_urls.is_empty() {
+            target_urls(args, scraper)?
+        } else {
+            args.compare_urls.clone()
+        };
+        return dry_run(&urls, scraper.config(), scraper.options(), &mut out);
+    }
+
+    if let [first, second] = args.compare_urls.as_slice() {
+        return compare_urls(args, scraper, [first, second], &mut out).await;
+    }
+
+    if args.format == OutputFormat::Ndjson {
+        return stream_ndjson(args, scraper, &mut out).await;
+    }
+
+    let mut failures = Vec::new();
+    let mut skipped_sources = 0;
+    let (scraped, target) = match args.url.as_slice() {
+        [url] if url != "-" => {
+            let headlines = scraper.fetch(url).await?;
+            (vec![(url.clone(), headlines)], url.clone())
+        }
+        _ => {
+            let urls = target_urls(args, scraper)?;
+
+            let progress = progress_bar(urls.len(), args.summary);
+            let mut results = std::pin::pin!(fetch_all(
+                scraper,
+                &urls,
+                args.concurrency as usize,
+                true,
+                &progress
+            ));
+            let mut outcomes = Vec::new();
+            while let Some((source, result)) = results.next().await {
+                if args.fail_fast {
+                    if let Some(Err(e)) = &result {
+                        progress.finish_and_clear();
+                        error!("Failed to fetch {}: {}", source, e);
+                        return Ok(ExitCode::FAILURE);
+                    }
+                }
+                outcomes.push((source, result));
+            }
+            progress.finish_and_clear();
+
+            // Failures are reported together once every fetch has finished
+            let mut scraped = Vec::new();
+            for (source, result) in outcomes {
+                let Some(result) = result else {
+                    skipped_sources += 1;
+                    continue;
+                };
+                match result {
+                    Ok(headlines) => scraped.push((source.to_string(), headlines)),
+                    Err(e) => {
+                        error!("Failed to fetch {}: {}", source, e);
+                        failures.push((source.to_string(), e));
+                    }
+                }
+            }
+            if skipped_sources > 0 {
+                warn!(
+                    "Interrupted; reporting {} of {} sources",
+                    urls.len() - skipped_sources - failures.len(),
+                    urls.len()
+                );
+            } else if !failures.is_empty() && failures.len() == urls.len() {
+                print_error_summary(&failures, &mut io::stderr())?;
+                eprintln!("Error: every source failed");
+                return Ok(ExitCode::FAILURE);
+            }
+            (scraped, "any source".to_string())
+        }
+    };
+
+    if args.count_only {
+        let counts: Vec<(&str, usize)> = scraped
+            .iter()
+            .map(|(source, headlines)| (source.as_str(), headlines.len()))
+            .collect();
+        print_headline_counts(&counts, &mut out)?;
+        return Ok(exit_code(
+            scraped.iter().all(|(_, headlines)| headlines.is_empty()),
+            &target,
+            &failures,
+            skipped_sources > 0,
+        ));
+    }
+
+    let mut sentiment_results = Vec::new();
+    let (mut foreign, mut analyzed) = (0, 0);
+    for (source, headlines) in &scraped {
+        let analysis = analyze_source(args, scraper, source, headlines);
+        foreign += analysis.foreign;
+        analyzed += analysis.analyzed;
+        // Sources whose headlines were all filtered out aren't empty
+        if headlines.is_empty() {
+            output_options.empty_sources.push(source.clone());
+        }
+        sentiment_results.extend(analysis.results);
+    }
+    warn_if_mostly_foreign(args, foreign, analyzed);
+
+    if args.top.is_some() || args.bottom.is_some() {
+        sentiment_results = keep_extremes(sentiment_results, args.top, args.bottom);
+    }
+    sort_results(&mut sentiment_results, args.sort);
+    #[cfg(feature = "metrics")]
+    if let Some(metrics) = &scraper.options().metrics {
+        let scores = sentiment_results
+            .iter()
+            .filter_map(|result| result["sentiment"].as_f64());
+        metrics.set_average_sentiment(mean(scores));
+    }
+
+    match &args.output_dir {
+        Some(dir) => write_output_dir(dir, &sentiment_results, &output_options)?,
+        None => print_sentiment_results(&sentiment_results, &output_options, &mut out)?,
+    }
+    if let Some(n) = args.phrase_ngrams.map(|n| n as usize) {
+        let headlines = sentiment_results
+            .iter()
+            .filter_map(|result| result["headline"].as_str());
+        let scores = ngram_scores(headlines, n, scraper.scorer());
+        if args.format == OutputFormat::Text {
+            print_phrase_extremes(&scores, n, PHRASE_EXTREMES, &mut out)?;
+        } else {
+            print_phrase_extremes(&scores, n, PHRASE_EXTREMES, &mut io::stderr())?;
+        }
+    }
+    if let Some(count) = args.tokens.map(|count| count as usize) {
+        let tokens = token_frequencies(sentiment_results.iter().filter_map(|result| {
+            Some((result["headline"].as_str()?, result["sentiment"].as_f64()?))
+        }));
+        if args.format == OutputFormat::Text {
+            print_token_frequencies(&tokens, count, &mut out)?;
+        } else {
+            print_token_frequencies(&tokens, count, &mut io::stderr())?;
+        }
+    }
+    if let Some(path) = &args.db {
+        let mut history = History::open(path)?;
+        if args.compare {
+            // Compare before recording, or the previous run would be this one
+            let mut changes = Vec::new();
+            for (source, _, average) in source_averages(&sentiment_results) {
+                changes.push((source.clone(), history.previous_average(&source)?, average));
+            }
+            // Keep structured formats parseable by sending the diff to stderr
+            if args.format == OutputFormat::Text {
+                print_comparison(&changes, &mut out)?;
             } else {
-                None
+                print_comparison(&changes, &mut io::stderr())?;
             }
-        })
+        }
+        history.record(&sentiment_results)?;
+    }
+    Ok(exit_code(
+        sentiment_results.is_empty(),
+        &target,
+        &failures,
+        skipped_sources > 0,
+    ))
+}
+
+/// Headlines from one source after sentiment analysis and the language and
+/// sentiment filters.
+struct SourceAnalysis {
+    results: Vec<HashMap<String, Value>>,
+    /// Headlines that looked non-English, counted before any filtering
+    foreign: usize,
+    /// Headlines analyzed, before any filtering
+    analyzed: usize,
+}
+
+fn analyze_source(
+    args: &Args,
+    scraper: &Scraper,
+    source: &str,
+    headlines: &[Headline],
+) -> SourceAnalysis {
+    let mut results: Vec<_> = scraper
+        .analyze(source, headlines)
+        .iter()
+        .map(SentimentResult::to_map)
         .collect();
+    let analyzed = results.len();
+    // Only after scoring: capitalization is an intensity cue for VADER
+    if args.case != HeadlineCase::AsIs {
+        for result in &mut results {
+            let headline = result["headline"].as_str().unwrap_or_default();
+            let cased = args.case.apply(headline);
+            result.insert("headline".to_string(), Value::String(cased));
+        }
+    }
+
+    // VADER is tuned for English, so scores for other languages are noise
+    let is_foreign = |result: &HashMap<String, Value>| {
+        result["language"]
+            .as_str()
+            .is_some_and(|code| code != "eng")
+    };
+    let foreign = results.iter().filter(|r| is_foreign(r)).count();
+    if args.lang_filter {
+        results.retain(|result| !is_foreign(result));
+    }
 
-    let mut data = HashMap::new();
-    data.insert("headlines".to_string(), headlines);
+    // Aggregates are computed over the filtered set, not everything scraped
+    results.retain(|result| {
+        let sentiment = result["sentiment"].as_f64().unwrap();
+        args.min_sentiment.is_none_or(|min| sentiment >= min)
+            && args.max_sentiment.is_none_or(|max| sentiment <= max)
+    });
 
-    Ok(data)
+    let baseline = if args.normalize_baseline && !results.is_empty() {
+        let total: f64 = results
+            .iter()
+            .map(|result| result["sentiment"].as_f64().unwrap())
+            .sum();
+        Some(total / results.len() as f64)
+    } else {
+        args.baseline
+    };
+    if let Some(baseline) = baseline {
+        for result in &mut results {
+            let sentiment = result["sentiment"].as_f64().unwrap() - baseline;
+            let label = sentiment_label(sentiment, args.neutral_band);
+            result.insert("sentiment".to_string(), Value::from(sentiment));
+            result.insert("label".to_string(), Value::from(label));
+        }
+    }
+    SourceAnalysis {
+        results,
+        foreign,
+        analyzed,
+    }
+}
+
+fn warn_if_mostly_foreign(args: &Args, foreign: usize, analyzed: usize) {
+    if !args.lang_filter && foreign > 0 && foreign * 5 >= analyzed {
+        warn!(
+            "{} of {} headlines look non-English; their sentiment scores are unreliable \
+             (use --lang-filter to skip them)",
+            foreign, analyzed
+        );
+    }
 }
 
-fn perform_sentiment_analysis(
-    headlines: &Vec<String>,
-) -> Result<Vec<HashMap<String, Value>>, ScraperError> {
-    let analyzer = SentimentIntensityAnalyzer::new();
-    let mut results = Vec::new();
+/// A source's headlines, or `None` when Ctrl-C cut its fetch short.
+type FetchOutcome = Option<Result<Vec<Headline>, ScraperError>>;
 
-    for headline in headlines {
-        let sentiment = analyzer.polarity_scores(headline);
-        let sentiment_value = sentiment.get("compound").unwrap_or(&0.0);
+/// Fetch every URL, at most `--concurrency` at a time, yielding results in
+/// source order when `ordered` and otherwise as soon as each completes.
+/// After Ctrl-C no new fetches start and in-flight ones get a short grace
+/// period; both yield `None`.
+fn fetch_all<'a>(
+    scraper: &'a Scraper,
+    urls: &'a [String],
+    concurrency: usize,
+    ordered: bool,
+    progress: &'a ProgressBar,
+) -> impl Stream<Item = (&'a str, FetchOutcome)> + 'a {
+    let interrupt = watch_for_interrupt();
+    let fetches = stream::iter(urls).map(move |source| {
+        let interrupt = interrupt.clone();
+        let mut interrupted = interrupt.clone();
+        async move {
+            if *interrupt.borrow() {
+                return (source.as_str(), None);
+            }
+            let grace = async {
+                let _ = interrupted.wait_for(|&interrupted| interrupted).await;
+                sleep(INTERRUPT_GRACE).await;
+            };
+            let result = tokio::select! {
+                result = async {
+                    progress.set_message(source.clone());
+                    let result = scraper.fetch(source).await;
+                    progress.inc(1);
+                    Some(result)
+                } => result,
+                _ = grace => None,
+            };
+            (source.as_str(), result)
+        }
+    });
+    if ordered {
+        fetches.buffered(concurrency).left_stream()
+    } else {
+        fetches.buffer_unordered(concurrency).right_stream()
+    }
+}
+
+/// `--compare-urls`: fetch both URLs concurrently and print their aggregates
+/// side by side.
+async fn compare_urls(
+    args: &Args,
+    scraper: &Scraper,
+    urls: [&str; 2],
+    out: &mut dyn io::Write,
+) -> Result<ExitCode, ScraperError> {
+    let (first, second) = tokio::join!(scraper.fetch(urls[0]), scraper.fetch(urls[1]));
+    let mut sides = Vec::new();
+    for (url, headlines) in urls.into_iter().zip([first?, second?]) {
+        let results = analyze_source(args, scraper, url, &headlines).results;
+        let average = source_averages(&results)
+            .first()
+            .map(|(_, _, average)| *average);
+        sides.push((url.to_string(), results.len(), average));
+    }
+    print_side_by_side(&sides, out)?;
+    Ok(exit_code(
+        sides.iter().all(|(_, count, _)| *count == 0),
+        "either URL",
+        &[],
+        false,
+    ))
+}
+
+/// `--format ndjson`: analyze and write each source's results as soon as it
+/// is fetched, keeping only per-source aggregates in memory.
+async fn stream_ndjson(
+    args: &Args,
+    scraper: &Scraper,
+    out: &mut dyn io::Write,
+) -> Result<ExitCode, ScraperError> {
+    // These need the complete result set before anything can be written
+    let unsupported = [
+        (args.sort != SortOrder::None, "--sort"),
+        (args.db.is_some(), "--db"),
+        (args.count_only, "--count-only"),
+        (args.bins.is_some(), "--bins"),
+        (args.weight_by_length, "--weight-by-length"),
+        (args.output_dir.is_some(), "--output-dir"),
+        (args.phrase_ngrams.is_some(), "--phrase-ngrams"),
+        (args.tokens.is_some(), "--tokens"),
+        (args.stats, "--stats"),
+        (args.top.is_some(), "--top"),
+        (args.bottom.is_some(), "--bottom"),
+    ];
+    if let Some((_, flag)) = unsupported.iter().find(|(set, _)| *set) {
+        return Err(ScraperError::UnsupportedWithStreaming(flag.to_string()));
+    }
+
+    let single = matches!(args.url.as_slice(), [url] if url != "-");
+    let urls = if single {
+        args.url.clone()
+    } else {
+        target_urls(args, scraper)?
+    };
+    let progress = progress_bar(urls.len(), args.summary || single);
+    let mut results = std::pin::pin!(fetch_all(
+        scraper,
+        &urls,
+        args.concurrency as usize,
+        // A slow source mustn't hold back ones that have already finished
+        false,
+        &progress,
+    ));
+
+    let mut sources = Vec::new();
+    let (mut failures, mut skipped_sources) = (Vec::new(), 0);
+    let (mut foreign, mut analyzed) = (0, 0);
+    // Empty sources still get a summary row, so count the headlines written
+    let mut emitted = 0;
+    while let Some((source, result)) = results.next().await {
+        let headlines = match result {
+            None => {
+                skipped_sources += 1;
+                continue;
+            }
+            Some(Ok(headlines)) => headlines,
+            Some(Err(e)) if single => return Err(e),
+            Some(Err(e)) if args.fail_fast => {
+                progress.finish_and_clear();
+                error!("Failed to fetch {}: {}", source, e);
+                return Ok(ExitCode::FAILURE);
+            }
+            Some(Err(e)) => {
+                error!("Failed to fetch {}: {}", source, e);
+                failures.push((source.to_string(), e));
+                continue;
+            }
+        };
+        let analysis = analyze_source(args, scraper, source, &headlines);
+        foreign += analysis.foreign;
+        analyzed += analysis.analyzed;
+        emitted += analysis.results.len();
+        if !args.summary {
+            progress.suspend(|| print_jsonl_headlines(&analysis.results, out))?;
+            out.flush()?;
+        }
+        if headlines.is_empty() {
+            let average = args.include_empty_sources.then_some(0.0);
+            sources.push((source.to_string(), 0, average));
+        }
+        sources.extend(
+            source_averages(&analysis.results)
+                .into_iter()
+                .map(|(source, count, average)| (source, count, Some(average))),
+        );
+    }
+    progress.finish_and_clear();
+    warn_if_mostly_foreign(args, foreign, analyzed);
 
-        let mut result = HashMap::new();
-        result.insert("headline".to_string(), Value::String(headline.clone()));
-        result.insert(
-            "sentiment".to_string(),
-            Value::Number(serde_json::Number::from_f64(*sentiment_value).unwrap()),
+    if skipped_sources > 0 {
+        warn!(
+            "Interrupted; reporting {} of {} sources",
+            urls.len() - skipped_sources - failures.len(),
+            urls.len()
         );
+    } else if !failures.is_empty() && failures.len() == urls.len() {
+        print_error_summary(&failures, &mut io::stderr())?;
+        eprintln!("Error: every source failed");
+        return Ok(ExitCode::FAILURE);
+    }
+    #[cfg(feature = "metrics")]
+    if let Some(metrics) = &scraper.options().metrics {
+        let scores = sources
+            .iter()
+            .filter_map(|(_, count, average)| Some(vec![(*average)?; *count]))
+            .flatten();
+        metrics.set_average_sentiment(mean(scores));
+    }
+    print_jsonl_summary(&sources, None, None, out)?;
+    out.flush()?;
+
+    let target = if single { &urls[0] } else { "any source" };
+    Ok(exit_code(
+        emitted == 0,
+        target,
+        &failures,
+        skipped_sources > 0,
+    ))
+}
 
-        results.push(result);
+/// Serve metrics on `--metrics-port` while `scrape` runs, then push them to
+/// `--metrics-pushgateway` and keep serving until Ctrl-C.
+#[cfg(feature = "metrics")]
+async fn with_metrics(
+    args: &Args,
+    scraper: &Scraper,
+    scrape: impl std::future::Future<Output = Result<ExitCode, ScraperError>>,
+) -> Result<ExitCode, ScraperError> {
+    let Some(metrics) = scraper.options().metrics.clone() else {
+        return scrape.await;
+    };
+    if let Some(port) = args.metrics_port {
+        metrics.serve((args.metrics_addr, port).into()).await?;
+    }
+    let result = scrape.await;
+    if let Some(gateway) = &args.metrics_pushgateway {
+        // The run's own outcome matters more than a monitoring hiccup
+        if let Err(e) = metrics.push(&Client::new(), gateway).await {
+            error!("Failed to push metrics to {}: {}", gateway, e);
+        }
     }
+    if let Some(port) = args.metrics_port {
+        eprintln!("Serving metrics on port {} until interrupted", port);
+        let _ = signal::ctrl_c().await;
+    }
+    result
+}
 
-    Ok(results)
+/// The mean of `scores`, if there are any.
+#[cfg(feature = "metrics")]
+fn mean(scores: impl Iterator<Item = f64>) -> Option<f64> {
+    let (total, count) = scores.fold((0.0, 0), |(total, count), score| (total + score, count + 1));
+    (count > 0).then(|| total / count as f64)
 }
 
-fn print_sentiment_results(results: &Vec<HashMap<String, Value>>) {
+/// Fetching and filtering behavior selected by the command-line flags.
+fn fetch_options(args: &Args) -> FetchOptions {
+    FetchOptions {
+        retries: args.retries,
+        retry_delay: Duration::from_millis(args.retry_delay),
+        retry_jitter: args.retry_jitter,
+        respect_robots: !args.ignore_robots,
+        robots: RobotsCache::default(),
+        user_agent: args.user_agent.clone(),
+        filter: HeadlineFilter {
+            min_words: args.min_words,
+            max_words: args.max_words,
+            include: args.include.clone(),
+            exclude: args.exclude.clone(),
+            decode_entities: !args.no_decode,
+            show_rejected: args.show_filtered,
+            enabled: !args.no_filter,
+            ..HeadlineFilter::default()
+        },
+        dedupe: !args.keep_duplicates,
+        dedupe_ignore_case: args.ignore_case,
+        limit: args.limit.filter(|&limit| limit > 0),
+        min_headlines: args.min_headlines,
+        sample: args.sample,
+        cache: args
+            .cache_dir
+            .as_ref()
+            .map(|dir| Cache::new(dir, Duration::from_secs(args.cache_ttl), args.no_cache)),
+        rate_limiter: (args.delay > 0).then(|| RateLimiter::new(Duration::from_millis(args.delay))),
+        circuit_breaker: args.circuit_breaker.map(CircuitBreaker::new),
+        retry_on_empty: args.retry_on_empty,
+        max_body_size: args.max_body_size,
+        user_agents: args
+            .user_agent_rotate
+            .then(|| UserAgentPool::new(&args.user_agent)),
+        allow_local: args.allow_local,
+        force_html: args.force_html,
+        rng: Arc::new(SharedRng::new(args.seed)),
+        strategy: if args.feed {
+            Strategy::Feed
+        } else {
+            args.strategy
+        },
+        extractor: args.extractor_cmd.as_ref().map(|command| {
+            ExternalExtractor::new(command, Duration::from_secs(args.extractor_timeout))
+        }),
+        #[cfg(feature = "metrics")]
+        metrics: (args.metrics_port.is_some() || args.metrics_pushgateway.is_some())
+            .then(Arc::default),
+    }
+}
+
+/// Build the HTTP client shared by every fetch in this run.
+///
+/// Without `--proxy`, reqwest picks up `HTTP_PROXY`/`HTTPS_PROXY` from the
+/// environment on its own.
+fn build_client(args: &Args) -> Result<Client, ScraperError> {
+    let mut builder = Client::builder()
+        .timeout(Duration::from_secs(args.timeout))
+        .user_agent(&args.user_agent)
+        .default_headers(default_headers(args))
+        // Sites' own cookies are kept for the run, so consent redirects stick
+        .cookie_provider(Arc::new(FixedCookies::new(args.cookie.clone())))
+        .redirect(match args.max_redirects {
+            // Without redirects the 3xx itself comes back and fails the status check
+            0 => redirect::Policy::none(),
+            limit => redirect::Policy::limited(limit),
+        })
+        // Compressed bodies would otherwise reach the HTML parser undecoded
+        .gzip(true)
+        .brotli(true)
+        .deflate(true);
+    if let Some(seconds) = args.connect_timeout {
+        builder = builder.connect_timeout(Duration::from_secs(seconds));
+    }
+    if let Some(proxy) = &args.proxy {
+        let proxy =
+            Proxy::all(proxy).map_err(|e| ScraperError::ProxyError(format!("{}: {}", proxy, e)))?;
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &args.ca_cert {
+        let certificate = fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|pem| Certificate::from_pem(&pem).map_err(|e| e.to_string()))
+            .map_err(|e| ScraperError::CertificateError(format!("{}: {}", path, e)))?;
+        builder = builder.add_root_certificate(certificate);
+    }
+    if args.danger_accept_invalid_certs {
+        warn!("TLS certificate validation is disabled");
+        builder = builder.danger_accept_invalid_certs(true);
+    }
+    Ok(builder.build()?)
+}
+
+/// `--output-dir`: write results grouped into one file per source domain.
+fn write_output_dir(
+    dir: &str,
+    results: &[HashMap<String, Value>],
+    options: &OutputOptions,
+) -> Result<(), ScraperError> {
+    let mut files: Vec<(String, Vec<HashMap<String, Value>>)> = Vec::new();
     for result in results {
-        println!(
-            "Headline: {}\nSentiment: {}\n",
-            result["headline"], result["sentiment"]
-        );
+        let name = output_file_name(result["source"].as_str().unwrap_or_default());
+        match files.iter_mut().find(|(file, _)| *file == name) {
+            Some((_, results)) => results.push(result.clone()),
+            None => files.push((name, vec![result.clone()])),
+        }
     }
 
-    let total_headlines = results.len();
-    let positive_headlines = results
-        .iter()
-        .filter(|result| result["sentiment"].as_f64().unwrap() > 0.05)
-        .count();
-    let negative_headlines = results
-        .iter()
-        .filter(|result| result["sentiment"].as_f64().unwrap() < -0.05)
-        .count();
-    let neutral_headlines = total_headlines - positive_headlines - negative_headlines;
+    // Each file covers one source, so the empty ones belong in none of them
+    let options = OutputOptions {
+        empty_sources: Vec::new(),
+        ..options.clone()
+    };
+    fs::create_dir_all(dir)?;
+    for (name, results) in &files {
+        let path = Path::new(dir).join(format!("{}.{}", name, options.format.extension()));
+        let mut out = open_output(path.to_str())?;
+        print_sentiment_results(results, &options, &mut out)?;
+        out.flush()?;
+    }
+    eprintln!("Wrote {} file(s) to {}", files.len(), dir);
+    Ok(())
+}
 
-    let average_sentiment: f64 = results
-        .iter()
-        .map(|result| result["sentiment"].as_f64().unwrap())
-        .sum::<f64>()
-        / total_headlines as f64;
-
-    println!("Overall Sentiment: {:.2}\n", average_sentiment);
-    println!("Total Headlines: {}", total_headlines);
-    println!(
-        "Positive Headlines: {} ({:.2}%)",
-        positive_headlines,
-        (positive_headlines as f64 / total_headlines as f64) * 100.0
-    );
-    println!(
-        "Negative Headlines: {} ({:.2}%)",
-        negative_headlines,
-        (negative_headlines as f64 / total_headlines as f64) * 100.0
-    );
-    println!(
-        "Neutral Headlines: {} ({:.2}%)",
-        neutral_headlines,
-        (neutral_headlines as f64 / total_headlines as f64) * 100.0
+/// The source's domain without a leading `www.`, or the whole source with
+/// anything unsafe in a file name replaced when it has no host.
+fn output_file_name(source: &str) -> String {
+    match Url::parse(source)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+    {
+        Some(host) => host.trim_start_matches("www.").to_string(),
+        None => source
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect(),
+    }
+}
+
+/// URLs selected by `--all`, `--urls-file` or `--url` (with `-` read from
+/// stdin), in a random order under `--shuffle-sources`. Listed file paths
+/// become `file://` URLs, as they do for `--url`.
+fn target_urls(args: &Args, scraper: &Scraper) -> Result<Vec<String>, ScraperError> {
+    let mut urls = if args.all {
+        scraper.config().sources.clone()
+    } else if let Some(path) = &args.urls_file {
+        let urls = parse_line_list(&fs::read_to_string(path)?);
+        urls.iter().map(|url| local_path_url(url)).collect()
+    } else {
+        let mut urls = Vec::new();
+        for url in &args.url {
+            if url == "-" {
+                let listed = parse_line_list(&io::read_to_string(io::stdin())?);
+                urls.extend(listed.iter().map(|url| local_path_url(url)));
+            } else {
+                urls.push(url.clone());
+            }
+        }
+        urls
+    };
+    if args.shuffle_sources {
+        scraper.options().rng.with(|rng| urls.shuffle(rng));
+    }
+    Ok(urls)
+}
+
+/// A `file://` URL for `url` if it is the path of an existing file rather
+/// than a URL, otherwise `url` itself.
+fn local_path_url(url: &str) -> String {
+    if Url::parse(url).is_ok() || url == "-" {
+        return url.to_string();
+    }
+    fs::canonicalize(url)
+        .ok()
+        .filter(|path| path.is_file())
+        .and_then(|path| Url::from_file_path(path).ok())
+        .map_or_else(|| url.to_string(), String::from)
+}
+
+/// Check every selector and target URL without making any requests,
+/// reporting problems to stderr.
+fn dry_run(
+    urls: &[String],
+    config: &Config,
+    options: &FetchOptions,
+    out: &mut dyn io::Write,
+) -> Result<ExitCode, ScraperError> {
+    let mut problems = 0;
+    for source in &config.selectors {
+        for selector in source.selector.iter().chain(&source.fallback_selectors) {
+            if Selector::parse(selector).is_err() {
+                eprintln!("{}", ScraperError::InvalidSelector(selector.clone()));
+                problems += 1;
+            }
+        }
+    }
+    for url in urls {
+        let problem = match validate_url(url, options.allow_local) {
+            Err(e) => Some(e.to_string()),
+            Ok(_)
+                if options.strategy == Strategy::Css
+                    && options.extractor.is_none()
+                    && config.source_for(url).is_none() =>
+            {
+                Some(ScraperError::UnsupportedDomain(url.clone()).to_string())
+            }
+            Ok(_) => None,
+        };
+        if let Some(problem) = problem {
+            eprintln!("{}", problem);
+            problems += 1;
+        }
+    }
+    writeln!(
+        out,
+        "Checked {} selectors and {} URLs: {} problem(s)",
+        config
+            .selectors
+            .iter()
+            .map(|source| source.selector.len() + source.fallback_selectors.len())
+            .sum::<usize>(),
+        urls.len(),
+        problems
+    )?;
+    out.flush()?;
+    Ok(if problems > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Map the outcome of a run onto the documented exit codes, summarizing any
+/// `failures` on stderr.
+fn exit_code(
+    no_headlines: bool,
+    target: &str,
+    failures: &[(String, ScraperError)],
+    interrupted: bool,
+) -> ExitCode {
+    if !failures.is_empty() {
+        // The run's output is already written; a summary failing to print
+        // shouldn't change its exit code
+        let _ = print_error_summary(failures, &mut io::stderr());
+    }
+    if interrupted {
+        ExitCode::from(EXIT_INTERRUPTED)
+    } else if no_headlines {
+        eprintln!("No headlines found for {}", target);
+        ExitCode::from(EXIT_NO_HEADLINES)
+    } else if !failures.is_empty() {
+        ExitCode::from(EXIT_PARTIAL_FAILURE)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Whether text output should be colored: only on a terminal, and never with
+/// `--no-color` or `NO_COLOR` (see no-color.org).
+fn use_color(args: &Args) -> bool {
+    !args.no_color
+        && args.output.is_none()
+        && args.output_dir.is_none()
+        && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        && io::stdout().is_terminal()
+}
+
+/// An `X/N` progress bar on stderr, hidden when stderr is not a terminal or
+/// output should be kept terse.
+fn progress_bar(total: usize, quiet: bool) -> ProgressBar {
+    if quiet || !io::stderr().is_terminal() {
+        return ProgressBar::hidden();
+    }
+    let progress = ProgressBar::new(total as u64);
+    progress.set_style(
+        ProgressStyle::with_template("{spinner} [{bar:30}] {pos}/{len} {wide_msg}")
+            .expect("progress template is valid")
+            .progress_chars("=> "),
     );
+    progress.enable_steady_tick(Duration::from_millis(100));
+    progress
+}
+
+/// Flip the returned flag to `true` on the first Ctrl-C; a second one exits
+/// immediately.
+fn watch_for_interrupt() -> watch::Receiver<bool> {
+    let (tx, rx) = watch::channel(false);
+    tokio::spawn(async move {
+        if signal::ctrl_c().await.is_err() {
+            return;
+        }
+        eprintln!("Interrupted; finishing in-flight requests (press Ctrl-C again to quit)");
+        let _ = tx.send(true);
+        if signal::ctrl_c().await.is_ok() {
+            std::process::exit(EXIT_INTERRUPTED.into());
+        }
+    });
+    rx
+}
+
+/// Accept-Language plus any `--header`s, which win over it. A name repeated
+/// in `--header` is sent once per value.
+fn default_headers(args: &Args) -> HeaderMap {
+    let mut headers = HeaderMap::from_iter([(ACCEPT_LANGUAGE, args.accept_language.clone())]);
+    // The `auth` group allows at most one of these
+    if let Some(credentials) = args.basic_auth.as_ref().or(args.bearer.as_ref()) {
+        headers.insert(AUTHORIZATION, credentials.clone());
+    }
+    let mut overridden = Vec::new();
+    for (name, value) in &args.header {
+        if overridden.contains(name) {
+            headers.append(name, value.clone());
+        } else {
+            headers.insert(name, value.clone());
+            overridden.push(name.clone());
+        }
+    }
+    headers
+}
+
+/// Parse a `--header`, which must be `key=value` with a valid header name.
+fn parse_header(value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (key, header_value) = value
+        .split_once('=')
+        .ok_or_else(|| format!("`{}` is not of the form key=value", value))?;
+    let name = HeaderName::from_bytes(key.trim().as_bytes())
+        .map_err(|_| format!("`{}` is not a valid header name", key))?;
+    Ok((name, parse_header_value(header_value.trim())?))
+}
+
+/// Parse `--basic-auth` credentials, which must be `user:pass`, into an
+/// `Authorization` header value.
+fn parse_basic_auth(value: &str) -> Result<HeaderValue, String> {
+    match value.split_once(':') {
+        Some((user, _)) if !user.is_empty() => {
+            authorization(&format!("Basic {}", BASE64_STANDARD.encode(value)))
+        }
+        _ => Err("expected credentials of the form user:pass".to_string()),
+    }
+}
+
+/// Parse a `--bearer` token into an `Authorization` header value.
+fn parse_bearer(value: &str) -> Result<HeaderValue, String> {
+    if value.trim().is_empty() {
+        return Err("the token is empty".to_string());
+    }
+    authorization(&format!("Bearer {}", value.trim()))
+}
+
+/// An `Authorization` header value, marked so it is never logged. Errors
+/// leave the credentials out too.
+fn authorization(value: &str) -> Result<HeaderValue, String> {
+    let mut value = HeaderValue::from_str(value)
+        .map_err(|_| "the credentials contain characters not allowed in a header".to_string())?;
+    value.set_sensitive(true);
+    Ok(value)
+}
+
+/// Where `--extract` reads each matched element's headline from.
+#[derive(Clone, Debug, PartialEq)]
+enum Extraction {
+    Text,
+    Attribute(String),
+}
+
+/// Parse `--extract`, which must be `text`, `href` or `attr:NAME`.
+fn parse_extract(value: &str) -> Result<Extraction, String> {
+    match value {
+        "text" => Ok(Extraction::Text),
+        "href" => Ok(Extraction::Attribute("href".to_string())),
+        _ => match value.strip_prefix("attr:").map(str::trim) {
+            Some("") => Err("attr: needs an attribute name, e.g. attr:aria-label".to_string()),
+            Some(name) => Ok(Extraction::Attribute(name.to_string())),
+            None => Err(format!("`{}` is not one of text, href or attr:NAME", value)),
+        },
+    }
+}
+
+/// Parse a `--cookie`, which must be `name=value` and valid in a header.
+fn parse_cookie(value: &str) -> Result<String, String> {
+    match value.split_once('=') {
+        Some((name, _)) if !name.trim().is_empty() => {
+            parse_header_value(value)?;
+            Ok(value.to_string())
+        }
+        _ => Err(format!("`{}` is not of the form name=value", value)),
+    }
+}
+
+fn parse_header_value(value: &str) -> Result<HeaderValue, String> {
+    HeaderValue::from_str(value).map_err(|_| format!("`{}` is not a valid header value", value))
+}
+
+/// Parse `--neutral-band`, which must lie in 0.0..=1.0.
+fn parse_neutral_band(value: &str) -> Result<f64, String> {
+    let band = parse_compound(value)?;
+    if band < 0.0 {
+        return Err(format!(
+            "{} is negative; the band is symmetric around zero",
+            band
+        ));
+    }
+    Ok(band)
+}
+
+/// Parse a VADER compound score bound, which must lie in -1.0..=1.0.
+fn parse_compound(value: &str) -> Result<f64, String> {
+    let score: f64 = value
+        .parse()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    if (-1.0..=1.0).contains(&score) {
+        Ok(score)
+    } else {
+        Err(format!(
+            "{} is outside the compound range -1.0 to 1.0",
+            score
+        ))
+    }
+}
+
+/// Send diagnostics to stderr so they never mix with results on stdout.
+///
+/// `-v` flags only raise this crate's level; dependencies stay at `warn`
+/// unless `RUST_LOG` says otherwise.
+fn init_logging(verbosity: u8) {
+    let level = match verbosity {
+        0 => "warn",
+        1 => "info",
+        2 => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,scraper_cli={}", level)));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+/// The `top` most positive and `bottom` most negative results, in their
+/// original order. A result at both ends is kept once.
+fn keep_extremes(
+    results: Vec<HashMap<String, Value>>,
+    top: Option<usize>,
+    bottom: Option<usize>,
+) -> Vec<HashMap<String, Value>> {
+    let sentiment = |i: &usize| results[*i]["sentiment"].as_f64().unwrap_or(0.0);
+    let mut ranked: Vec<usize> = (0..results.len()).collect();
+    ranked.sort_by(|a, b| {
+        sentiment(b)
+            .partial_cmp(&sentiment(a))
+            .unwrap_or(Ordering::Equal)
+    });
+    let mut keep = vec![false; results.len()];
+    for &i in ranked.iter().take(top.unwrap_or(0)) {
+        keep[i] = true;
+    }
+    for &i in ranked.iter().rev().take(bottom.unwrap_or(0)) {
+        keep[i] = true;
+    }
+    results
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(result, keep)| keep.then_some(result))
+        .collect()
+}
+
+/// Sort by compound sentiment. The sort is stable, so ties keep scrape order.
+fn sort_results(results: &mut [HashMap<String, Value>], order: SortOrder) {
+    let compare = |a: &HashMap<String, Value>, b: &HashMap<String, Value>| {
+        let a = a["sentiment"].as_f64().unwrap_or(0.0);
+        let b = b["sentiment"].as_f64().unwrap_or(0.0);
+        a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+    };
+    match order {
+        SortOrder::Asc => results.sort_by(compare),
+        SortOrder::Desc => results.sort_by(|a, b| compare(b, a)),
+        SortOrder::None => {}
+    }
 }
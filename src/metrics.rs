@@ -0,0 +1,207 @@
+use reqwest::Client;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::warn;
+
+use crate::ScraperError;
+
+/// Upper bounds, in seconds, of the request latency histogram's buckets.
+const LATENCY_BUCKETS: [f64; 9] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Counters for one run, rendered in the Prometheus text exposition format.
+#[derive(Default)]
+pub struct Metrics {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Headline count per scraped source, or `None` if its fetch failed
+    sources: BTreeMap<String, Option<usize>>,
+    /// Requests per latency bucket, plus the overflow past the last bound
+    latency_counts: [u64; LATENCY_BUCKETS.len() + 1],
+    latency_sum: f64,
+    average_sentiment: Option<f64>,
+}
+
+impl Metrics {
+    /// Note how long one HTTP request took, whatever its outcome.
+    pub fn record_request(&self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let mut state = self.state.lock().unwrap();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        state.latency_counts[bucket] += 1;
+        state.latency_sum += seconds;
+    }
+
+    /// Note that `source` yielded `headlines`, or failed when `None`.
+    pub fn record_source(&self, source: &str, headlines: Option<usize>) {
+        let mut state = self.state.lock().unwrap();
+        state.sources.insert(source.to_string(), headlines);
+    }
+
+    pub fn set_average_sentiment(&self, average: Option<f64>) {
+        self.state.lock().unwrap().average_sentiment = average;
+    }
+
+    /// Every metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let state = self.state.lock().unwrap();
+        let failed = state.sources.values().filter(|h| h.is_none()).count();
+        let mut out = String::new();
+        // Each metric's samples must directly follow its HELP and TYPE lines
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} {}", name, help, name, kind);
+            for (suffix, value) in samples {
+                let _ = writeln!(out, "{}{} {}", name, suffix, value);
+            }
+        };
+        let sample =
+            |suffix: &str, value: &dyn std::fmt::Display| (suffix.to_string(), value.to_string());
+
+        metric(
+            "scraper_sources",
+            "gauge",
+            "Sources scraped in the last run.",
+            &[sample("", &state.sources.len())],
+        );
+        metric(
+            "scraper_source_successes",
+            "gauge",
+            "Sources fetched successfully in the last run.",
+            &[sample("", &(state.sources.len() - failed))],
+        );
+        metric(
+            "scraper_source_failures",
+            "gauge",
+            "Sources that failed in the last run.",
+            &[sample("", &failed)],
+        );
+        let headlines: Vec<_> = state
+            .sources
+            .iter()
+            .filter_map(|(source, headlines)| {
+                let suffix = format!("{{source=\"{}\"}}", escape_label(source));
+                Some(sample(&suffix, &(*headlines)?))
+            })
+            .collect();
+        metric(
+            "scraper_headlines",
+            "gauge",
+            "Headlines extracted per source in the last run.",
+            &headlines,
+        );
+
+        let mut latency = Vec::new();
+        let mut cumulative = 0;
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(&state.latency_counts) {
+            cumulative += count;
+            latency.push(sample(&format!("_bucket{{le=\"{}\"}}", bound), &cumulative));
+        }
+        let total: u64 = state.latency_counts.iter().sum();
+        latency.push(sample("_bucket{le=\"+Inf\"}", &total));
+        latency.push(sample("_sum", &state.latency_sum));
+        latency.push(sample("_count", &total));
+        metric(
+            "scraper_request_duration_seconds",
+            "histogram",
+            "Latency of each HTTP request attempt, including retries.",
+            &latency,
+        );
+
+        let average: Vec<_> = state
+            .average_sentiment
+            .iter()
+            .map(|average| sample("", average))
+            .collect();
+        metric(
+            "scraper_average_sentiment",
+            "gauge",
+            "Overall compound sentiment of the last run.",
+            &average,
+        );
+        out
+    }
+
+    /// Answer every HTTP request on `addr` with the current metrics, in the
+    /// background, until the process exits.
+    pub async fn serve(self: &Arc<Self>, addr: SocketAddr) -> Result<(), ScraperError> {
+        let listener = TcpListener::bind(addr).await?;
+        let metrics = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    continue;
+                };
+                let body = metrics.render();
+                tokio::spawn(async move {
+                    // Whatever was asked for, the answer is the metrics page
+                    let mut request = [0; 1024];
+                    let _ = stream.read(&mut request).await;
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    );
+                    if let Err(e) = stream.write_all(response.as_bytes()).await {
+                        warn!("Failed to serve metrics: {}", e);
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+
+    /// Replace this job's metrics on the Prometheus Pushgateway at `gateway`.
+    pub async fn push(&self, client: &Client, gateway: &str) -> Result<(), ScraperError> {
+        let url = format!("{}/metrics/job/scraper_cli", gateway.trim_end_matches('/'));
+        let response = client.put(&url).body(self.render()).send().await?;
+        if !response.status().is_success() {
+            return Err(ScraperError::HttpStatus {
+                url,
+                code: response.status().as_u16(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Backslashes, quotes and newlines would end a label value early.
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_counts_and_cumulative_buckets() {
+        let metrics = Metrics::default();
+        metrics.record_source("bbc.com", Some(12));
+        metrics.record_source("cnn.com", None);
+        metrics.record_request(Duration::from_millis(80));
+        metrics.record_request(Duration::from_secs(3));
+        metrics.set_average_sentiment(Some(0.25));
+
+        let text = metrics.render();
+        assert!(text.contains("scraper_sources 2\n"));
+        assert!(text.contains("scraper_source_failures 1\n"));
+        assert!(text.contains("scraper_headlines{source=\"bbc.com\"} 12\n"));
+        assert!(text.contains("scraper_request_duration_seconds_bucket{le=\"0.1\"} 1\n"));
+        assert!(text.contains("scraper_request_duration_seconds_bucket{le=\"5\"} 2\n"));
+        assert!(text.contains("scraper_request_duration_seconds_count 2\n"));
+        assert!(text.contains("scraper_average_sentiment 0.25\n"));
+    }
+}
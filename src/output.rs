@@ -0,0 +1,1227 @@
+use clap::ValueEnum;
+use owo_colors::OwoColorize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+
+use crate::ScraperError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    Text,
+    /// A single JSON document
+    Json,
+    /// One compact JSON object per headline, then a `"type": "summary"` object
+    Jsonl,
+    /// Like `jsonl`, but each source's lines are written and flushed as soon
+    /// as it has been analyzed instead of after the whole run
+    Ndjson,
+    /// Comma-separated values with a `headline,sentiment,source,label,link` header
+    Csv,
+    /// Tab-separated values with the same columns as CSV
+    Tsv,
+    /// A Markdown table
+    Markdown,
+    /// A self-contained HTML report with a color-coded table
+    Html,
+}
+
+impl OutputFormat {
+    /// File extension used for files written in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Text => "txt",
+            OutputFormat::Json => "json",
+            OutputFormat::Jsonl | OutputFormat::Ndjson => "jsonl",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Tsv => "tsv",
+            OutputFormat::Markdown => "md",
+            OutputFormat::Html => "html",
+        }
+    }
+}
+
+/// Casing applied to headlines for display and export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum HeadlineCase {
+    /// Keep the casing the site used
+    AsIs,
+    Lower,
+    Upper,
+    /// Capitalize the first letter of every word
+    Title,
+}
+
+impl HeadlineCase {
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            HeadlineCase::AsIs => text.to_string(),
+            HeadlineCase::Lower => text.to_lowercase(),
+            HeadlineCase::Upper => text.to_uppercase(),
+            HeadlineCase::Title => text
+                .split(' ')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    match chars.next() {
+                        Some(first) => first
+                            .to_uppercase()
+                            .chain(chars.flat_map(char::to_lowercase))
+                            .collect(),
+                        None => String::new(),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+        }
+    }
+}
+
+/// Controls how results are rendered.
+#[derive(Clone)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    /// Include the per-headline positive/negative/neutral scores in text output
+    pub verbose: bool,
+    /// Skip per-headline output and print only the aggregates
+    pub summary: bool,
+    /// Print a histogram of compound scores with this many bins in text output
+    pub bins: Option<usize>,
+    /// Also report the overall sentiment weighted by headline word count
+    pub weight_by_length: bool,
+    /// Color text output by sentiment label
+    pub color: bool,
+    /// Also report the median and standard deviation of compound scores
+    pub stats: bool,
+    /// Start CSV and TSV output with a header row; off when appending to a
+    /// file that already has one
+    pub headers: bool,
+    /// Sources that were scraped but yielded no headlines, listed in the
+    /// per-source aggregates with zero headlines
+    pub empty_sources: Vec<String>,
+    /// Count each empty source as one neutral score in the overall
+    /// sentiment instead of leaving it out
+    pub include_empty_sources: bool,
+}
+
+/// Spread of the compound scores, reported alongside the mean by `--stats`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoreStats {
+    pub median: f64,
+    /// Population standard deviation
+    pub stddev: f64,
+}
+
+fn score_stats(results: &[HashMap<String, Value>]) -> Option<ScoreStats> {
+    let mean = average_sentiment(results)?;
+    let mut scores: Vec<f64> = results
+        .iter()
+        .map(|result| result["sentiment"].as_f64().unwrap())
+        .collect();
+    scores.sort_by(|a, b| a.total_cmp(b));
+    let middle = scores.len() / 2;
+    let median = if scores.len().is_multiple_of(2) {
+        (scores[middle - 1] + scores[middle]) / 2.0
+    } else {
+        scores[middle]
+    };
+    let variance = scores
+        .iter()
+        .map(|score| (score - mean).powi(2))
+        .sum::<f64>()
+        / scores.len() as f64;
+    Some(ScoreStats {
+        median,
+        stddev: variance.sqrt(),
+    })
+}
+
+/// Mean compound score where each headline counts once per word, so longer
+/// headlines weigh more.
+fn weighted_sentiment(results: &[HashMap<String, Value>]) -> Option<f64> {
+    let words = |result: &HashMap<String, Value>| result["words"].as_u64().unwrap_or(1) as f64;
+    let total_words: f64 = results.iter().map(words).sum();
+    if total_words == 0.0 {
+        return None;
+    }
+    let total: f64 = results
+        .iter()
+        .map(|result| result["sentiment"].as_f64().unwrap() * words(result))
+        .sum();
+    Some(total / total_words)
+}
+
+/// The mean compound score, with any empty sources counted as neutral when
+/// `options` asks for it.
+fn overall_sentiment(results: &[HashMap<String, Value>], options: &OutputOptions) -> Option<f64> {
+    if !options.include_empty_sources || options.empty_sources.is_empty() {
+        return average_sentiment(results);
+    }
+    let total: f64 = results
+        .iter()
+        .map(|result| result["sentiment"].as_f64().unwrap())
+        .sum();
+    Some(total / (results.len() + options.empty_sources.len()) as f64)
+}
+
+fn average_sentiment(results: &[HashMap<String, Value>]) -> Option<f64> {
+    if results.is_empty() {
+        return None;
+    }
+    let total: f64 = results
+        .iter()
+        .map(|result| result["sentiment"].as_f64().unwrap())
+        .sum();
+    Some(total / results.len() as f64)
+}
+
+/// Open the destination for results: the `--output` file if given, otherwise stdout.
+pub fn open_output(path: Option<&str>) -> Result<Box<dyn Write>, ScraperError> {
+    Ok(match path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    })
+}
+
+/// Open `path` for adding results after any already in it, returning
+/// whether it had some. A last record missing its newline is terminated
+/// first, so the appended ones start on a line of their own.
+pub fn append_output(path: &str) -> Result<(Box<dyn Write>, bool), ScraperError> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+    let len = file.metadata()?.len();
+    if len > 0 {
+        let mut last = [0];
+        file.seek(SeekFrom::Start(len - 1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            file.write_all(b"\n")?;
+        }
+    }
+    Ok((Box::new(BufWriter::new(file)), len > 0))
+}
+
+/// Average sentiment per source as `(source, headline count, average)`, in
+/// the order sources first appear in `results`.
+pub fn source_averages(results: &[HashMap<String, Value>]) -> Vec<(String, usize, f64)> {
+    let mut averages: Vec<(String, usize, f64)> = Vec::new();
+    for result in results {
+        let source = result["source"].as_str().unwrap_or_default();
+        let sentiment = result["sentiment"].as_f64().unwrap();
+        match averages.iter_mut().find(|(name, _, _)| name == source) {
+            Some((_, count, total)) => {
+                *count += 1;
+                *total += sentiment;
+            }
+            None => averages.push((source.to_string(), 1, sentiment)),
+        }
+    }
+    for (_, count, total) in averages.iter_mut() {
+        *total /= *count as f64;
+    }
+    averages
+}
+
+/// `(source, headline count, average)` for every source in `results`, then
+/// for each empty source, whose average is neutral if it counts towards the
+/// overall sentiment and missing otherwise.
+fn source_rows(
+    results: &[HashMap<String, Value>],
+    options: &OutputOptions,
+) -> Vec<(String, usize, Option<f64>)> {
+    let mut rows: Vec<_> = source_averages(results)
+        .into_iter()
+        .map(|(source, count, average)| (source, count, Some(average)))
+        .collect();
+    let average = options.include_empty_sources.then_some(0.0);
+    rows.extend(
+        options
+            .empty_sources
+            .iter()
+            .map(|source| (source.clone(), 0, average)),
+    );
+    rows
+}
+
+pub fn print_sentiment_results(
+    results: &Vec<HashMap<String, Value>>,
+    options: &OutputOptions,
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    let weighted = options
+        .weight_by_length
+        .then(|| weighted_sentiment(results))
+        .flatten();
+    let stats = options.stats.then(|| score_stats(results)).flatten();
+    let sources = source_rows(results, options);
+    let overall = overall_sentiment(results, options);
+    match (options.format, options.summary) {
+        (OutputFormat::Text, summary) => {
+            if summary {
+                print_text_summary(&sources, overall, out)?;
+            } else {
+                print_text(results, &sources, overall, options, out)?;
+            }
+            if let Some(weighted) = weighted {
+                writeln!(out, "Length-Weighted Sentiment: {:.2}", weighted)?;
+            }
+            if let Some(stats) = stats {
+                writeln!(out, "Median Sentiment: {:.2}", stats.median)?;
+                writeln!(out, "Sentiment Std Dev: {:.2}", stats.stddev)?;
+            }
+            if let Some(bins) = options.bins.filter(|_| !results.is_empty()) {
+                print_histogram(&histogram(results, bins), out)?;
+            }
+        }
+        (OutputFormat::Json, summary) => {
+            print_json(results, &sources, overall, summary, weighted, stats, out)?
+        }
+        (OutputFormat::Jsonl | OutputFormat::Ndjson, summary) => {
+            if !summary {
+                print_jsonl_headlines(results, out)?;
+            }
+            print_jsonl_summary(&sources, weighted, stats, out)?
+        }
+        (OutputFormat::Csv, false) => print_delimited(results, b',', options.headers, out)?,
+        (OutputFormat::Csv, true) => print_delimited_summary(&sources, b',', options.headers, out)?,
+        (OutputFormat::Tsv, false) => print_delimited(results, b'\t', options.headers, out)?,
+        (OutputFormat::Tsv, true) => {
+            print_delimited_summary(&sources, b'\t', options.headers, out)?
+        }
+        (OutputFormat::Markdown, false) => print_markdown(results, out)?,
+        (OutputFormat::Markdown, true) => print_markdown_summary(&sources, out)?,
+        (OutputFormat::Html, summary) => {
+            print_html(results, &sources, overall, summary, weighted, stats, out)?
+        }
+    }
+    match options.format {
+        // Delimited output keeps its aggregates on stderr
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            if let Some(average) = overall {
+                eprintln!("Overall Sentiment: {:.2}", average);
+            }
+            if let Some(weighted) = weighted {
+                eprintln!("Length-Weighted Sentiment: {:.2}", weighted);
+            }
+            if let Some(stats) = stats {
+                eprintln!("Median Sentiment: {:.2}", stats.median);
+                eprintln!("Sentiment Std Dev: {:.2}", stats.stddev);
+            }
+        }
+        OutputFormat::Markdown => {
+            if let Some(average) = overall {
+                writeln!(out, "\n**Overall Sentiment:** {:.2}", average)?;
+            }
+            if let Some(weighted) = weighted {
+                writeln!(out, "\n**Length-Weighted Sentiment:** {:.2}", weighted)?;
+            }
+            if let Some(stats) = stats {
+                writeln!(out, "\n**Median Sentiment:** {:.2}", stats.median)?;
+                writeln!(out, "\n**Sentiment Std Dev:** {:.2}", stats.stddev)?;
+            }
+        }
+        OutputFormat::Text
+        | OutputFormat::Json
+        | OutputFormat::Jsonl
+        | OutputFormat::Ndjson
+        | OutputFormat::Html => {}
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn source_summaries(sources: &[(String, usize, Option<f64>)]) -> Vec<Value> {
+    sources
+        .iter()
+        .map(|(source, count, average)| {
+            serde_json::json!({
+                "source": source,
+                "headlines": count,
+                "average_sentiment": average,
+            })
+        })
+        .collect()
+}
+
+/// Write one `"type": "headline"` line per result.
+pub fn print_jsonl_headlines(
+    results: &[HashMap<String, Value>],
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    for result in results {
+        let mut line = serde_json::json!({ "type": "headline" });
+        for (key, value) in result {
+            line[key] = value.clone();
+        }
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// Write the closing `"type": "summary"` line from per-source
+/// `(source, headlines, average)` aggregates. Sources without an average
+/// are left out of the overall sentiment; an empty source with one counts
+/// as a single headline.
+pub fn print_jsonl_summary(
+    sources: &[(String, usize, Option<f64>)],
+    weighted: Option<f64>,
+    stats: Option<ScoreStats>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let (total, weight) = sources
+        .iter()
+        .filter_map(|(_, count, average)| Some(((*count).max(1) as f64, (*average)?)))
+        .fold((0.0, 0.0), |(total, weight), (count, average)| {
+            (total + count * average, weight + count)
+        });
+    let overall = (weight > 0.0).then(|| total / weight);
+    let mut line = serde_json::json!({
+        "type": "summary",
+        "sources": source_summaries(sources),
+        "overall_sentiment": overall,
+    });
+    if let Some(weighted) = weighted {
+        line["weighted_sentiment"] = weighted.into();
+    }
+    insert_stats(&mut line, stats);
+    writeln!(out, "{}", line)
+}
+
+fn insert_stats(summary: &mut Value, stats: Option<ScoreStats>) {
+    if let Some(stats) = stats {
+        summary["median_sentiment"] = stats.median.into();
+        summary["sentiment_stddev"] = stats.stddev.into();
+    }
+}
+
+fn print_json(
+    results: &Vec<HashMap<String, Value>>,
+    sources: &[(String, usize, Option<f64>)],
+    overall: Option<f64>,
+    summary: bool,
+    weighted: Option<f64>,
+    stats: Option<ScoreStats>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut output = serde_json::json!({
+        "sources": source_summaries(sources),
+        "overall_sentiment": overall,
+    });
+    if let Some(weighted) = weighted {
+        output["weighted_sentiment"] = weighted.into();
+    }
+    insert_stats(&mut output, stats);
+    if !summary {
+        output["results"] = serde_json::json!(results);
+    }
+    writeln!(out, "{}", serde_json::to_string_pretty(&output).unwrap())
+}
+
+fn print_delimited(
+    results: &Vec<HashMap<String, Value>>,
+    delimiter: u8,
+    headers: bool,
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(out);
+    if headers {
+        writer.write_record(["headline", "sentiment", "source", "label", "link"])?;
+    }
+    for result in results {
+        writer.write_record([
+            result["headline"].as_str().unwrap_or_default(),
+            &result["sentiment"].to_string(),
+            result["source"].as_str().unwrap_or_default(),
+            result["label"].as_str().unwrap_or_default(),
+            result["link"].as_str().unwrap_or_default(),
+        ])?;
+    }
+    writer.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+/// One `source,headlines,average_sentiment` row per source.
+fn print_delimited_summary(
+    sources: &[(String, usize, Option<f64>)],
+    delimiter: u8,
+    headers: bool,
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(out);
+    if headers {
+        writer.write_record(["source", "headlines", "average_sentiment"])?;
+    }
+    for (source, count, average) in sources {
+        writer.write_record([
+            source,
+            &count.to_string(),
+            &average
+                .map(|average| average.to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+    writer.flush().map_err(csv::Error::from)?;
+    Ok(())
+}
+
+fn print_markdown(results: &Vec<HashMap<String, Value>>, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "| Headline | Sentiment | Label | Source |")?;
+    writeln!(out, "| --- | ---: | --- | --- |")?;
+    for result in results {
+        writeln!(
+            out,
+            "| {} | {} | {} | {} |",
+            escape_markdown_cell(result["headline"].as_str().unwrap_or_default()),
+            result["sentiment"],
+            result["label"].as_str().unwrap_or_default(),
+            escape_markdown_cell(result["source"].as_str().unwrap_or_default()),
+        )?;
+    }
+    Ok(())
+}
+
+fn print_markdown_summary(
+    sources: &[(String, usize, Option<f64>)],
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(out, "| Source | Headlines | Sentiment |")?;
+    writeln!(out, "| --- | ---: | ---: |")?;
+    for (source, count, average) in sources {
+        writeln!(
+            out,
+            "| {} | {} | {} |",
+            escape_markdown_cell(source),
+            count,
+            format_average(*average)
+        )?;
+    }
+    Ok(())
+}
+
+/// A per-source average to two decimals, or `-` for a source without one.
+fn format_average(average: Option<f64>) -> String {
+    average.map_or_else(|| "-".to_string(), |average| format!("{:.2}", average))
+}
+
+const HTML_STYLE: &str =
+    "body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 60em; color: #222; }
+table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }
+th, td { border-bottom: 1px solid #ddd; padding: 0.4em 0.6em; text-align: left; }
+td.score { text-align: right; font-variant-numeric: tabular-nums; }
+tr.positive td.score { color: #1a7f37; }
+tr.negative td.score { color: #cf222e; }
+tr.neutral td.score { color: #6e7781; }
+.summary { font-size: 1.2em; }";
+
+/// A standalone page with the aggregates up top, then the per-source table
+/// and, unless `summary` is set, every headline.
+fn print_html(
+    results: &[HashMap<String, Value>],
+    sources: &[(String, usize, Option<f64>)],
+    overall: Option<f64>,
+    summary: bool,
+    weighted: Option<f64>,
+    stats: Option<ScoreStats>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>Headline Sentiment</title>")?;
+    writeln!(out, "<style>\n{}\n</style>\n</head>\n<body>", HTML_STYLE)?;
+    writeln!(out, "<h1>Headline Sentiment</h1>")?;
+    let mut figures = vec![("Overall Sentiment", format_average(overall))];
+    if let Some(weighted) = weighted {
+        figures.push(("Length-Weighted Sentiment", format!("{:.2}", weighted)));
+    }
+    if let Some(stats) = stats {
+        figures.push(("Median Sentiment", format!("{:.2}", stats.median)));
+        figures.push(("Sentiment Std Dev", format!("{:.2}", stats.stddev)));
+    }
+    for (name, value) in figures {
+        writeln!(
+            out,
+            "<p class=\"summary\"><strong>{}:</strong> {}</p>",
+            name, value
+        )?;
+    }
+
+    writeln!(
+        out,
+        "<table>\n<tr><th>Source</th><th>Headlines</th><th>Sentiment</th></tr>"
+    )?;
+    for (source, count, average) in sources {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td class=\"score\">{}</td><td class=\"score\">{}</td></tr>",
+            html_escape::encode_text(source),
+            count,
+            format_average(*average)
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    if !summary {
+        writeln!(
+            out,
+            "<table>\n<tr><th>Headline</th><th>Sentiment</th><th>Label</th><th>Source</th></tr>"
+        )?;
+        for result in results {
+            let headline =
+                html_escape::encode_text(result["headline"].as_str().unwrap_or_default());
+            // Only web links, so a scraped `javascript:` href can't run in the report
+            let headline = match result["link"]
+                .as_str()
+                .filter(|link| link.starts_with("http://") || link.starts_with("https://"))
+            {
+                Some(link) => format!(
+                    "<a href=\"{}\">{}</a>",
+                    html_escape::encode_double_quoted_attribute(link),
+                    headline
+                ),
+                None => headline.into_owned(),
+            };
+            let label = result["label"].as_str().unwrap_or_default();
+            writeln!(
+                out,
+                "<tr class=\"{}\"><td>{}</td><td class=\"score\">{:.2}</td><td>{}</td><td>{}</td></tr>",
+                html_escape::encode_double_quoted_attribute(label),
+                headline,
+                result["sentiment"].as_f64().unwrap_or_default(),
+                html_escape::encode_text(label),
+                html_escape::encode_text(result["source"].as_str().unwrap_or_default()),
+            )?;
+        }
+        writeln!(out, "</table>")?;
+    }
+    writeln!(out, "</body>\n</html>")
+}
+
+/// Pipes would split the cell and newlines would end the row.
+fn escape_markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+/// `text` in green, red or gray for a positive, negative or neutral label.
+fn paint(text: &str, label: &str, color: bool) -> String {
+    if !color {
+        return text.to_string();
+    }
+    match label {
+        "positive" => text.green().to_string(),
+        "negative" => text.red().to_string(),
+        _ => text.bright_black().to_string(),
+    }
+}
+
+fn print_text(
+    results: &Vec<HashMap<String, Value>>,
+    sources: &[(String, usize, Option<f64>)],
+    overall: Option<f64>,
+    options: &OutputOptions,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let Some(average_sentiment) = overall.filter(|_| !results.is_empty()) else {
+        return Ok(());
+    };
+    let (verbose, color) = (options.verbose, options.color);
+
+    for result in results {
+        let label = result["label"].as_str().unwrap_or_default();
+        writeln!(
+            out,
+            "Headline: {}\nSource: {}\nSentiment: {}",
+            paint(&result["headline"].to_string(), label, color),
+            result["source"].as_str().unwrap_or_default(),
+            paint(
+                &format!("{} ({})", result["sentiment"], label),
+                label,
+                color
+            )
+        )?;
+        if verbose {
+            writeln!(
+                out,
+                "Positive: {}, Negative: {}, Neutral: {}",
+                result["positive"], result["negative"], result["neutral"]
+            )?;
+        }
+        writeln!(out)?;
+    }
+
+    let total_headlines = results.len();
+    let labelled = |label: &str| {
+        results
+            .iter()
+            .filter(|result| result["label"].as_str() == Some(label))
+            .count()
+    };
+    let positive_headlines = labelled("positive");
+    let negative_headlines = labelled("negative");
+    let neutral_headlines = total_headlines - positive_headlines - negative_headlines;
+
+    if sources.len() > 1 {
+        print_source_table(sources, out)?;
+    }
+    writeln!(out, "Overall Sentiment: {:.2}\n", average_sentiment)?;
+    writeln!(out, "Total Headlines: {}", total_headlines)?;
+    writeln!(
+        out,
+        "Positive Headlines: {} ({:.2}%)",
+        positive_headlines,
+        (positive_headlines as f64 / total_headlines as f64) * 100.0
+    )?;
+    writeln!(
+        out,
+        "Negative Headlines: {} ({:.2}%)",
+        negative_headlines,
+        (negative_headlines as f64 / total_headlines as f64) * 100.0
+    )?;
+    writeln!(
+        out,
+        "Neutral Headlines: {} ({:.2}%)",
+        neutral_headlines,
+        (neutral_headlines as f64 / total_headlines as f64) * 100.0
+    )?;
+    Ok(())
+}
+
+/// Print the per-source table (when there is more than one source) and the
+/// overall average, nothing else.
+fn print_text_summary(
+    sources: &[(String, usize, Option<f64>)],
+    overall: Option<f64>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let Some(average_sentiment) = overall else {
+        return Ok(());
+    };
+    if sources.len() > 1 {
+        print_source_table(sources, out)?;
+    }
+    writeln!(out, "Overall Sentiment: {:.2}", average_sentiment)
+}
+
+/// Bucket compound scores into `bins` equal-width ranges spanning -1.0..=1.0,
+/// as `(low, high, count)`.
+fn histogram(results: &[HashMap<String, Value>], bins: usize) -> Vec<(f64, f64, usize)> {
+    let width = 2.0 / bins as f64;
+    let mut counts = vec![0; bins];
+    for result in results {
+        let sentiment = result["sentiment"].as_f64().unwrap();
+        // A score of exactly 1.0 belongs in the last bin
+        let bin = (((sentiment + 1.0) / width) as usize).min(bins - 1);
+        counts[bin] += 1;
+    }
+    counts
+        .into_iter()
+        .enumerate()
+        .map(|(i, count)| {
+            let low = -1.0 + i as f64 * width;
+            (low, low + width, count)
+        })
+        .collect()
+}
+
+/// Print one `low to high | bar count` row per bin, scaling bars to the largest bin.
+fn print_histogram(bins: &[(f64, f64, usize)], out: &mut dyn Write) -> io::Result<()> {
+    const BAR_WIDTH: usize = 40;
+    let max = bins
+        .iter()
+        .map(|(_, _, count)| *count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    writeln!(out, "\nSentiment Distribution:")?;
+    for (low, high, count) in bins {
+        let bar = "#".repeat((count * BAR_WIDTH).div_ceil(max));
+        writeln!(out, "{:>5.2} to {:>5.2} | {} {}", low, high, bar, count)?;
+    }
+    Ok(())
+}
+
+/// Print a `source | headlines | average` table, one row per source.
+fn print_source_table(
+    sources: &[(String, usize, Option<f64>)],
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let width = sources
+        .iter()
+        .map(|(source, _, _)| source.len())
+        .max()
+        .unwrap_or(0)
+        .max("Source".len());
+
+    writeln!(
+        out,
+        "{:<width$}  {:>9}  {:>9}",
+        "Source", "Headlines", "Sentiment"
+    )?;
+    for (source, count, average) in sources {
+        writeln!(
+            out,
+            "{:<width$}  {:>9}  {:>9}",
+            source,
+            count,
+            format_average(*average)
+        )?;
+    }
+    writeln!(out)
+}
+
+/// Print how many headlines each source produced, plus the total.
+pub fn print_headline_counts(
+    counts: &[(&str, usize)],
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    let width = counts
+        .iter()
+        .map(|(source, _)| source.len())
+        .max()
+        .unwrap_or(0)
+        .max("Total".len());
+    let mut total = 0;
+    for (source, count) in counts {
+        writeln!(out, "{:<width$}  {:>5}", source, count)?;
+        total += count;
+    }
+    writeln!(out, "{:<width$}  {:>5}", "Total", total)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Print a `url | kind | error` table of the sources that failed, so they
+/// can be read at a glance once the individual logs have scrolled away.
+pub fn print_error_summary(
+    failures: &[(String, ScraperError)],
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    let url_width = failures
+        .iter()
+        .map(|(url, _)| url.len())
+        .max()
+        .unwrap_or(0)
+        .max("URL".len());
+    let kind_width = failures
+        .iter()
+        .map(|(_, error)| error.kind().len())
+        .max()
+        .unwrap_or(0)
+        .max("Kind".len());
+    writeln!(
+        out,
+        "\nFailed sources:\n{:<url_width$}  {:<kind_width$}  Error",
+        "URL", "Kind"
+    )?;
+    for (url, error) in failures {
+        writeln!(
+            out,
+            "{:<url_width$}  {:<kind_width$}  {}",
+            url,
+            error.kind(),
+            error
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Print `--history` rows as a `date | headlines | average` table.
+pub fn print_history(
+    days: &[(String, usize, f64)],
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    if !days.is_empty() {
+        writeln!(
+            out,
+            "{:<10}  {:>9}  {:>9}",
+            "Date", "Headlines", "Sentiment"
+        )?;
+    }
+    for (day, count, average) in days {
+        writeln!(out, "{:<10}  {:>9}  {:>9.2}", day, count, average)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Bars for a sparkline, lowest first.
+const SPARK_BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// One bar per value, scaled between the smallest and largest so that small
+/// day-to-day moves stay visible. Equal values draw a flat middle line.
+pub fn sparkline(values: &[f64]) -> String {
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    values
+        .iter()
+        .map(|value| {
+            let level = if max > min {
+                ((value - min) / (max - min) * (SPARK_BARS.len() - 1) as f64).round() as usize
+            } else {
+                SPARK_BARS.len() / 2 - 1
+            };
+            SPARK_BARS[level]
+        })
+        .collect()
+}
+
+/// Print `--sparkline` for `--history` rows (most recent first), e.g.
+/// `https://www.bbc.com  ▂▅█▃  -0.12 → 0.08 over 4 days`.
+pub fn print_sparkline(
+    source: &str,
+    days: &[(String, usize, f64)],
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    let averages: Vec<f64> = days.iter().rev().map(|(_, _, average)| *average).collect();
+    if let (Some(first), Some(last)) = (averages.first(), averages.last()) {
+        writeln!(
+            out,
+            "{}  {}  {:.2} → {:.2} over {} day{}",
+            source,
+            sparkline(&averages),
+            first,
+            last,
+            averages.len(),
+            if averages.len() == 1 { "" } else { "s" }
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Print the `count` most positive and most negative `n`-word phrases from
+/// `scores`, which must be sorted most positive first. Neutral phrases are
+/// never listed.
+pub fn print_phrase_extremes(
+    scores: &[(String, f64)],
+    n: usize,
+    count: usize,
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    let positive: Vec<_> = scores
+        .iter()
+        .take_while(|(_, s)| *s > 0.0)
+        .take(count)
+        .collect();
+    let negative: Vec<_> = scores
+        .iter()
+        .rev()
+        .take_while(|(_, s)| *s < 0.0)
+        .take(count)
+        .collect();
+    for (title, phrases) in [("positive", positive), ("negative", negative)] {
+        writeln!(out, "\nMost {} {}-grams:", title, n)?;
+        if phrases.is_empty() {
+            writeln!(out, "  (none)")?;
+        }
+        for (phrase, score) in phrases {
+            writeln!(out, "  {:>5.2}  {}", score, phrase)?;
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// The `count` most common words from `(word, count, average)` token
+/// frequencies, with the average sentiment of the headlines containing each.
+pub fn print_token_frequencies(
+    tokens: &[(String, usize, f64)],
+    count: usize,
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    writeln!(out, "\nMost common words:")?;
+    if tokens.is_empty() {
+        writeln!(out, "  (none)")?;
+    }
+    for (word, occurrences, average) in tokens.iter().take(count) {
+        writeln!(out, "  {:>5}  {:>5.2}  {}", occurrences, average, word)?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Two sources' `(source, headline count, average)` in adjacent columns,
+/// followed by which one is more positive.
+pub fn print_side_by_side(
+    sides: &[(String, usize, Option<f64>)],
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    let width = sides
+        .iter()
+        .map(|(source, _, _)| source.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(8);
+    let average =
+        |average: &Option<f64>| average.map_or("n/a".to_string(), |a| format!("{:.2}", a));
+    write!(out, "{:<18}", "")?;
+    for (source, _, _) in sides {
+        write!(out, "  {:>width$}", source, width = width)?;
+    }
+    write!(out, "\n{:<18}", "Headlines")?;
+    for (_, count, _) in sides {
+        write!(out, "  {:>width$}", count, width = width)?;
+    }
+    write!(out, "\n{:<18}", "Average sentiment")?;
+    for (_, _, mean) in sides {
+        write!(out, "  {:>width$}", average(mean), width = width)?;
+    }
+    writeln!(out)?;
+
+    if let [(first, _, Some(a)), (second, _, Some(b))] = sides {
+        match a.partial_cmp(b) {
+            Some(Ordering::Greater) => writeln!(out, "{} is more positive by {:.2}", first, a - b)?,
+            Some(Ordering::Less) => writeln!(out, "{} is more positive by {:.2}", second, b - a)?,
+            _ => writeln!(out, "Both are equally positive")?,
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Print how each source's average moved since the previous recorded run,
+/// e.g. `bbc.com: 0.12 → -0.04, down 0.16`.
+pub fn print_comparison(
+    changes: &[(String, Option<f64>, f64)],
+    out: &mut dyn Write,
+) -> Result<(), ScraperError> {
+    writeln!(out, "\nChange since previous run:")?;
+    for (source, previous, current) in changes {
+        match previous {
+            Some(previous) => {
+                let delta = current - previous;
+                let direction = if delta > 0.0 {
+                    "up"
+                } else if delta < 0.0 {
+                    "down"
+                } else {
+                    "unchanged"
+                };
+                writeln!(
+                    out,
+                    "{}: {:.2} → {:.2}, {} {:.2}",
+                    source,
+                    previous,
+                    current,
+                    direction,
+                    delta.abs()
+                )?;
+            }
+            None => writeln!(out, "{}: {:.2} (no previous run)", source, current)?,
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(sentiment: f64) -> HashMap<String, Value> {
+        HashMap::from([("sentiment".to_string(), Value::from(sentiment))])
+    }
+
+    #[test]
+    fn weighted_sentiment_favours_longer_headlines() {
+        let mut short = scored(1.0);
+        short.insert("words".to_string(), Value::from(2));
+        let mut long = scored(-1.0);
+        long.insert("words".to_string(), Value::from(6));
+        assert_eq!(average_sentiment(&[short.clone(), long.clone()]), Some(0.0));
+        assert_eq!(weighted_sentiment(&[short, long]), Some(-0.5));
+    }
+
+    #[test]
+    fn side_by_side_names_the_more_positive_source() {
+        let sides = [
+            ("a.com".to_string(), 3, Some(-0.1)),
+            ("b.com".to_string(), 5, Some(0.25)),
+        ];
+        let mut out = Vec::new();
+        print_side_by_side(&sides, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("Headlines"));
+        assert!(text.ends_with("b.com is more positive by 0.35\n"));
+    }
+
+    #[test]
+    fn only_colored_output_gets_escape_codes() {
+        assert_eq!(paint("Storm hits", "negative", false), "Storm hits");
+        assert_eq!(
+            paint("Storm hits", "negative", true),
+            "\u{1b}[31mStorm hits\u{1b}[39m"
+        );
+    }
+
+    #[test]
+    fn stats_report_median_and_population_stddev() {
+        let results = [scored(-0.5), scored(0.1), scored(0.3), scored(0.9)];
+        let stats = score_stats(&results).unwrap();
+        assert!((stats.median - 0.2).abs() < 1e-9);
+        assert!((stats.stddev - 0.5).abs() < 1e-9);
+        assert_eq!(score_stats(&[]), None);
+    }
+
+    #[test]
+    fn title_case_capitalizes_each_word() {
+        assert_eq!(
+            HeadlineCase::Title.apply("STOCKS slide as rates rise"),
+            "Stocks Slide As Rates Rise"
+        );
+    }
+
+    #[test]
+    fn histogram_buckets_the_full_compound_range() {
+        let results = [
+            scored(-1.0),
+            scored(-0.2),
+            scored(0.0),
+            scored(0.7),
+            scored(1.0),
+        ];
+        let counts: Vec<usize> = histogram(&results, 4)
+            .into_iter()
+            .map(|(_, _, count)| count)
+            .collect();
+        assert_eq!(counts, vec![1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn appending_continues_on_a_new_line() {
+        use std::fs;
+
+        let path = std::env::temp_dir().join(format!("scraper_cli-append-{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "headline,sentiment\nOld,0.1").unwrap();
+
+        let (mut out, existing) = append_output(path).unwrap();
+        assert!(existing);
+        let results = vec![HashMap::from([
+            ("headline".to_string(), Value::from("New")),
+            ("sentiment".to_string(), Value::from(0.5)),
+            ("source".to_string(), Value::from("bbc.com")),
+            ("label".to_string(), Value::from("positive")),
+            ("link".to_string(), Value::Null),
+        ])];
+        print_delimited(&results, b',', false, &mut out).unwrap();
+        drop(out);
+        let text = fs::read_to_string(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(
+            text,
+            "headline,sentiment\nOld,0.1\nNew,0.5,bbc.com,positive,\n"
+        );
+    }
+
+    #[test]
+    fn empty_sources_are_listed_and_optionally_counted() {
+        let mut result = scored(0.6);
+        result.insert("source".to_string(), Value::from("bbc.com"));
+        let results = vec![result.clone(), result];
+        let mut options = OutputOptions {
+            format: OutputFormat::Json,
+            verbose: false,
+            summary: true,
+            bins: None,
+            weight_by_length: false,
+            color: false,
+            stats: false,
+            headers: true,
+            empty_sources: vec!["cnn.com".to_string()],
+            include_empty_sources: false,
+        };
+        let summary = |options: &OutputOptions| {
+            let mut out = Vec::new();
+            print_sentiment_results(&results, options, &mut out).unwrap();
+            serde_json::from_slice::<Value>(&out).unwrap()
+        };
+
+        let excluded = summary(&options);
+        assert_eq!(excluded["sources"][1]["source"], "cnn.com");
+        assert_eq!(excluded["sources"][1]["headlines"], 0);
+        assert_eq!(excluded["sources"][1]["average_sentiment"], Value::Null);
+        assert_eq!(excluded["overall_sentiment"].as_f64(), Some(0.6));
+
+        options.include_empty_sources = true;
+        let included = summary(&options);
+        assert_eq!(
+            included["sources"][1]["average_sentiment"].as_f64(),
+            Some(0.0)
+        );
+        assert_eq!(included["overall_sentiment"].as_f64(), Some(0.4));
+    }
+
+    #[test]
+    fn html_reports_escape_headlines_and_unsafe_links() {
+        let result = HashMap::from([
+            (
+                "headline".to_string(),
+                Value::from("<b>Rates</b> & \"rallies\""),
+            ),
+            ("sentiment".to_string(), Value::from(0.5)),
+            ("label".to_string(), Value::from("positive")),
+            ("source".to_string(), Value::from("bbc.com")),
+            ("link".to_string(), Value::from("javascript:alert(1)")),
+        ]);
+        let mut out = Vec::new();
+        print_html(&[result], &[], Some(0.5), false, None, None, &mut out).unwrap();
+        let html = String::from_utf8(out).unwrap();
+        assert!(html.contains("&lt;b&gt;Rates&lt;/b&gt; &amp;"));
+        assert!(html.contains("<tr class=\"positive\">"));
+        assert!(!html.contains("javascript:"));
+        assert!(html.trim_end().ends_with("</html>"));
+    }
+
+    #[test]
+    fn error_summary_lists_each_failure_with_its_kind() {
+        let failures = vec![
+            (
+                "https://slow.example".to_string(),
+                ScraperError::Timeout("https://slow.example".to_string()),
+            ),
+            (
+                "https://bbc.com".to_string(),
+                ScraperError::HttpStatus {
+                    url: "https://bbc.com".to_string(),
+                    code: 404,
+                },
+            ),
+        ];
+        let mut out = Vec::new();
+        print_error_summary(&failures, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[1], "Failed sources:");
+        assert!(lines[2].starts_with("URL                   Kind         Error"));
+        assert!(lines[3].starts_with("https://slow.example  timeout      Request to"));
+        assert!(lines[4].ends_with("http status  https://bbc.com returned HTTP status 404"));
+    }
+
+    #[test]
+    fn sparklines_scale_between_the_extremes() {
+        assert_eq!(sparkline(&[-0.5, 0.0, 0.5, 0.25]), "▁▅█▆");
+        assert_eq!(sparkline(&[0.1, 0.1]), "▄▄");
+        assert_eq!(sparkline(&[]), "");
+
+        let days = [
+            ("2026-10-02".to_string(), 4, 0.3),
+            ("2026-10-01".to_string(), 2, -0.1),
+        ];
+        let mut out = Vec::new();
+        print_sparkline("https://www.bbc.com", &days, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "https://www.bbc.com  ▁█  -0.10 → 0.30 over 2 days\n"
+        );
+    }
+}
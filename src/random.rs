@@ -0,0 +1,45 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Mutex;
+
+/// The run's single source of randomness, so that `--seed` makes every
+/// random choice reproducible. Draws made by concurrent fetches can still
+/// interleave differently unless `--concurrency` is 1.
+pub struct SharedRng(Mutex<StdRng>);
+
+impl SharedRng {
+    /// Seeded from the OS's entropy source when `seed` is `None`.
+    pub fn new(seed: Option<u64>) -> Self {
+        let rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        SharedRng(Mutex::new(rng))
+    }
+
+    /// Run `f` with exclusive use of the generator.
+    pub fn with<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        f(&mut self.0.lock().unwrap())
+    }
+}
+
+impl Default for SharedRng {
+    fn default() -> Self {
+        SharedRng::new(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn equal_seeds_draw_equal_sequences() {
+        let draw =
+            |rng: &SharedRng| rng.with(|rng| (0..4).map(|_| rng.gen()).collect::<Vec<u32>>());
+        let (a, b) = (SharedRng::new(Some(7)), SharedRng::new(Some(7)));
+        assert_eq!(draw(&a), draw(&b));
+        assert_ne!(draw(&a), draw(&SharedRng::new(Some(8))));
+    }
+}
@@ -0,0 +1,43 @@
+use reqwest::Url;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+
+/// Spaces out requests to the same host by at least `delay`, while requests
+/// to different hosts proceed independently.
+pub struct RateLimiter {
+    delay: Duration,
+    /// Earliest time the next request to each host may start
+    next_slot: Mutex<HashMap<String, Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(delay: Duration) -> Self {
+        RateLimiter {
+            delay,
+            next_slot: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Wait until a request to `url`'s host is allowed.
+    pub async fn wait(&self, url: &str) {
+        let Some(host) = Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+        else {
+            return;
+        };
+
+        // Reserve a slot under the lock, then sleep without holding it so
+        // concurrent fetches to the same host queue up one delay apart
+        let slot = {
+            let mut next_slot = self.next_slot.lock().unwrap();
+            let now = Instant::now();
+            let slot = next_slot.get(&host).map_or(now, |&next| next.max(now));
+            next_slot.insert(host, slot + self.delay);
+            slot
+        };
+        sleep_until(slot).await;
+    }
+}
@@ -0,0 +1,190 @@
+use reqwest::Url;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OnceCell;
+
+/// Each site's parsed `robots.txt`, fetched at most once per run however
+/// many of its pages are scraped.
+#[derive(Default)]
+pub struct RobotsCache {
+    /// Rules per origin; concurrent fetches from one site share a single
+    /// download of its `robots.txt`
+    origins: Mutex<HashMap<String, Arc<OnceCell<Vec<Group>>>>>,
+}
+
+impl RobotsCache {
+    /// Check the site's `robots.txt` to see whether `user_agent` may fetch
+    /// `url`, downloading it with `fetch` the first time the site is seen.
+    ///
+    /// A missing or unreachable `robots.txt`, for which `fetch` returns
+    /// `None`, allows everything, matching how crawlers conventionally treat it.
+    pub async fn is_allowed<F, Fut>(&self, url: &str, user_agent: &str, fetch: F) -> bool
+    where
+        F: FnOnce(Url) -> Fut,
+        Fut: Future<Output = Option<String>>,
+    {
+        let Ok(target) = Url::parse(url) else {
+            return true;
+        };
+        let Ok(robots_url) = target.join("/robots.txt") else {
+            return true;
+        };
+
+        let cell = Arc::clone(
+            self.origins
+                .lock()
+                .unwrap()
+                .entry(target.origin().ascii_serialization())
+                .or_default(),
+        );
+        let groups = cell
+            .get_or_init(|| async {
+                fetch(robots_url)
+                    .await
+                    .map(|body| parse_groups(&body))
+                    .unwrap_or_default()
+            })
+            .await;
+
+        let mut path = target.path().to_string();
+        if let Some(query) = target.query() {
+            path.push('?');
+            path.push_str(query);
+        }
+        let allowed = path_allowed(groups, &path, user_agent);
+        tracing::debug!(allowed, "checked robots.txt");
+        allowed
+    }
+}
+
+struct Group {
+    agents: Vec<String>,
+    /// `(allow, pattern)` pairs in file order
+    rules: Vec<(bool, String)>,
+}
+
+fn parse_groups(body: &str) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut in_agent_block = false;
+
+    for line in body.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim().to_string();
+
+        match key.as_str() {
+            "user-agent" => {
+                // Consecutive User-agent lines share one group of rules
+                if !in_agent_block {
+                    groups.push(Group {
+                        agents: Vec::new(),
+                        rules: Vec::new(),
+                    });
+                    in_agent_block = true;
+                }
+                // An empty name would be a substring of every agent
+                if let Some(group) = groups.last_mut().filter(|_| !value.is_empty()) {
+                    group.agents.push(value.to_ascii_lowercase());
+                }
+            }
+            "allow" | "disallow" => {
+                in_agent_block = false;
+                if let Some(group) = groups.last_mut() {
+                    // An empty Disallow means "allow everything"
+                    if !value.is_empty() {
+                        group.rules.push((key == "allow", value));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    groups
+}
+
+fn path_allowed(groups: &[Group], path: &str, user_agent: &str) -> bool {
+    let user_agent = user_agent.to_ascii_lowercase();
+
+    // Prefer a group naming our agent, falling back to the wildcard group
+    let group = groups
+        .iter()
+        .find(|group| {
+            group
+                .agents
+                .iter()
+                .any(|agent| agent != "*" && user_agent.contains(agent.as_str()))
+        })
+        .or_else(|| {
+            groups
+                .iter()
+                .find(|group| group.agents.iter().any(|a| a == "*"))
+        });
+    let Some(group) = group else {
+        return true;
+    };
+
+    // The longest matching rule wins; Allow wins ties
+    let mut best: Option<(usize, bool)> = None;
+    for (allow, pattern) in &group.rules {
+        if rule_matches(pattern, path) {
+            let better = match best {
+                None => true,
+                Some((len, best_allow)) => {
+                    pattern.len() > len || (pattern.len() == len && *allow && !best_allow)
+                }
+            };
+            if better {
+                best = Some((pattern.len(), *allow));
+            }
+        }
+    }
+    best.is_none_or(|(_, allow)| allow)
+}
+
+/// Match a robots.txt path pattern, supporting `*` wildcards and a trailing `$`.
+fn rule_matches(pattern: &str, path: &str) -> bool {
+    // Patterns are prefix matches unless anchored with `$`
+    let pattern = match pattern.strip_suffix('$') {
+        Some(anchored) => anchored.to_string(),
+        None => format!("{}*", pattern),
+    };
+    let (pattern, path) = (pattern.as_bytes(), path.as_bytes());
+
+    // Classic wildcard matching, backtracking to the most recent `*`
+    let (mut p, mut s) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while s < path.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, s));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == path[s] {
+            p += 1;
+            s += 1;
+        } else if let Some((star_p, star_s)) = star {
+            p = star_p + 1;
+            s = star_s + 1;
+            star = Some((star_p, star_s + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_agent_names_match_no_one() {
+        let groups = parse_groups("User-agent:\nDisallow: /\n\nUser-agent: *\nAllow: /\n");
+        assert!(path_allowed(&groups, "/news", "scraper_cli/1.0"));
+        let groups = parse_groups("User-agent: scraper\nDisallow: /news\n");
+        assert!(!path_allowed(&groups, "/news", "scraper_cli/1.0"));
+    }
+}
@@ -0,0 +1,368 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use vader_sentiment::SentimentIntensityAnalyzer;
+
+use crate::extract::Headline;
+use crate::ScraperError;
+
+/// VADER's conventional cut-off between neutral and polar compound scores.
+pub const DEFAULT_NEUTRAL_BAND: f64 = 0.05;
+
+/// Common English words too frequent to say anything about a day's topics.
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "an", "and", "are", "as", "at", "be", "been", "before",
+    "but", "by", "can", "could", "did", "do", "does", "for", "from", "had", "has", "have", "he",
+    "her", "his", "how", "i", "if", "in", "into", "is", "it", "its", "just", "more", "my", "new",
+    "no", "not", "now", "of", "off", "on", "one", "or", "our", "out", "over", "says", "she", "so",
+    "than", "that", "the", "their", "them", "then", "there", "they", "this", "to", "up", "us",
+    "was", "we", "were", "what", "when", "where", "which", "who", "why", "will", "with", "would",
+    "you", "your",
+];
+
+/// VADER's constant for squashing a raw sum of word scores into -1.0..=1.0.
+const NORMALIZATION_ALPHA: f64 = 15.0;
+
+/// The largest valence a custom lexicon may assign, VADER's own scale.
+const MAX_VALENCE: f64 = 4.0;
+
+/// Which scorer rates headlines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SentimentModel {
+    /// VADER's general-purpose lexicon and rules
+    Vader,
+    /// A custom word-to-score lexicon loaded with --lexicon
+    Lexicon,
+}
+
+/// Scores for one piece of text, on VADER's scales.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Polarity {
+    /// -1.0 (most negative) to 1.0 (most positive)
+    pub compound: f64,
+    /// Shares of the text that read as positive, negative and neutral
+    pub positive: f64,
+    pub negative: f64,
+    pub neutral: f64,
+}
+
+/// Rates the sentiment of a headline.
+pub trait Scorer: Send + Sync {
+    fn polarity(&self, text: &str) -> Polarity;
+}
+
+/// The VADER sentiment analyzer.
+pub struct Vader(SentimentIntensityAnalyzer<'static>);
+
+impl Default for Vader {
+    fn default() -> Self {
+        Vader(SentimentIntensityAnalyzer::new())
+    }
+}
+
+impl Scorer for Vader {
+    fn polarity(&self, text: &str) -> Polarity {
+        let scores = self.0.polarity_scores(text);
+        let score = |key: &str| scores.get(key).copied().unwrap_or(0.0);
+        Polarity {
+            compound: score("compound"),
+            positive: score("pos"),
+            negative: score("neg"),
+            neutral: score("neu"),
+        }
+    }
+}
+
+/// A domain-specific lexicon mapping words to valence scores, in VADER's
+/// format: one `word score` pair per line, with anything after the score
+/// ignored and `#` starting a comment line.
+pub struct Lexicon {
+    scores: HashMap<String, f64>,
+}
+
+impl Lexicon {
+    pub fn load(path: &str) -> Result<Self, ScraperError> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|e| ScraperError::LexiconError(format!("{}: {}", path, e)))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut scores = HashMap::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let (Some(word), Some(score)) = (fields.next(), fields.next()) else {
+                return Err(format!("line {}: expected a word and a score", number + 1));
+            };
+            let score: f64 = score
+                .parse()
+                .map_err(|_| format!("line {}: `{}` is not a number", number + 1, score))?;
+            // Infinite or NaN scores would make every compound score NaN
+            if !(-MAX_VALENCE..=MAX_VALENCE).contains(&score) {
+                return Err(format!(
+                    "line {}: score `{}` is outside -{} to {}",
+                    number + 1,
+                    score,
+                    MAX_VALENCE,
+                    MAX_VALENCE
+                ));
+            }
+            scores.insert(word.to_lowercase(), score);
+        }
+        Ok(Lexicon { scores })
+    }
+}
+
+impl Scorer for Lexicon {
+    /// The summed scores of the words found in the lexicon, normalized as
+    /// VADER does; words not in it count as neutral.
+    fn polarity(&self, text: &str) -> Polarity {
+        let (mut sum, mut positive, mut negative, mut neutral) = (0.0, 0.0, 0.0, 0.0);
+        for word in words(text) {
+            let score = self
+                .scores
+                .get(&word.to_lowercase())
+                .copied()
+                .unwrap_or(0.0);
+            sum += score;
+            if score > 0.0 {
+                positive += score;
+            } else if score < 0.0 {
+                negative -= score;
+            } else {
+                neutral += 1.0;
+            }
+        }
+        let total = positive + negative + neutral;
+        let share = |part: f64| if total > 0.0 { part / total } else { 0.0 };
+        Polarity {
+            compound: (sum / (sum * sum + NORMALIZATION_ALPHA).sqrt()).clamp(-1.0, 1.0),
+            positive: share(positive),
+            negative: share(negative),
+            neutral: share(neutral),
+        }
+    }
+}
+
+/// Words of `text` with surrounding punctuation (but not apostrophes) removed.
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric() && c != '\''))
+        .filter(|word| !word.is_empty())
+}
+
+/// Sentiment scores for one headline.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SentimentResult {
+    pub source: String,
+    pub headline: String,
+    pub link: Option<String>,
+    /// Compound score from -1.0 (most negative) to 1.0 (most positive)
+    pub sentiment: f64,
+    /// `positive`, `negative` or `neutral`, from the compound score
+    pub label: &'static str,
+    pub words: usize,
+    pub positive: f64,
+    pub negative: f64,
+    pub neutral: f64,
+    /// ISO 639-3 code, when the language could be detected reliably
+    pub language: Option<&'static str>,
+}
+
+impl SentimentResult {
+    /// The result keyed by field name, as consumed by the output writers.
+    pub fn to_map(&self) -> HashMap<String, Value> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(fields)) => fields.into_iter().collect(),
+            _ => unreachable!("SentimentResult serializes to an object"),
+        }
+    }
+}
+
+/// Classify a compound score, using VADER's conventional ±0.05 cut-offs by
+/// default.
+pub fn sentiment_label(compound: f64, neutral_band: f64) -> &'static str {
+    if compound >= neutral_band {
+        "positive"
+    } else if compound <= -neutral_band {
+        "negative"
+    } else {
+        "neutral"
+    }
+}
+
+/// ISO 639-3 code of the headline's language, when whatlang is confident.
+/// Headlines are short, so unreliable guesses are treated as unknown.
+fn detect_language(headline: &str) -> Option<&'static str> {
+    whatlang::detect(headline)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().code())
+}
+
+/// Score every headline from `source`, labelling compound scores within
+/// `neutral_band` of zero as neutral.
+pub fn analyze_headlines(
+    source: &str,
+    headlines: &[Headline],
+    scorer: &dyn Scorer,
+    neutral_band: f64,
+) -> Vec<SentimentResult> {
+    headlines
+        .iter()
+        .map(|headline| {
+            let polarity = scorer.polarity(&headline.text);
+            SentimentResult {
+                source: source.to_string(),
+                headline: headline.text.clone(),
+                link: headline.link.clone(),
+                sentiment: polarity.compound,
+                label: sentiment_label(polarity.compound, neutral_band),
+                words: headline.text.split_whitespace().count(),
+                positive: polarity.positive,
+                negative: polarity.negative,
+                neutral: polarity.neutral,
+                language: detect_language(&headline.text),
+            }
+        })
+        .collect()
+}
+
+/// Every distinct run of `n` consecutive words across `headlines` with its
+/// compound score, most positive first. Punctuation around words is dropped
+/// and phrases differing only in case are scored once.
+pub fn ngram_scores<'a>(
+    headlines: impl IntoIterator<Item = &'a str>,
+    n: usize,
+    scorer: &dyn Scorer,
+) -> Vec<(String, f64)> {
+    let mut seen = HashSet::new();
+    let mut scores = Vec::new();
+    for headline in headlines {
+        let words: Vec<&str> = words(headline).collect();
+        for window in words.windows(n) {
+            let phrase = window.join(" ");
+            if !seen.insert(phrase.to_lowercase()) {
+                continue;
+            }
+            let compound = scorer.polarity(&phrase).compound;
+            scores.push((phrase, compound));
+        }
+    }
+    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    scores
+}
+
+/// How often each word other than stopwords and numbers appears across
+/// `(headline, compound)` pairs, with the average score of the headlines
+/// containing it, as `(word, count, average)`, most common first. Words are
+/// compared case-insensitively and without a possessive `'s`.
+pub fn token_frequencies<'a>(
+    headlines: impl IntoIterator<Item = (&'a str, f64)>,
+) -> Vec<(String, usize, f64)> {
+    // word -> (occurrences, summed score, headlines containing it)
+    let mut tokens: HashMap<String, (usize, f64, usize)> = HashMap::new();
+    for (headline, compound) in headlines {
+        let mut seen = HashSet::new();
+        for word in words(headline) {
+            let word = word.to_lowercase();
+            let word = word.strip_suffix("'s").unwrap_or(&word);
+            if !word.chars().any(char::is_alphabetic) || STOPWORDS.contains(&word) {
+                continue;
+            }
+            let entry = tokens.entry(word.to_string()).or_default();
+            entry.0 += 1;
+            if seen.insert(word.to_string()) {
+                entry.1 += compound;
+                entry.2 += 1;
+            }
+        }
+    }
+    let mut frequencies: Vec<_> = tokens
+        .into_iter()
+        .map(|(word, (count, total, headlines))| (word, count, total / headlines as f64))
+        .collect();
+    frequencies.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    frequencies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_map_onto_the_output_fields() {
+        let headlines = [Headline {
+            text: "Wonderful news for everyone".to_string(),
+            link: None,
+        }];
+        let results = analyze_headlines(
+            "bbc.com",
+            &headlines,
+            &Vader::default(),
+            DEFAULT_NEUTRAL_BAND,
+        );
+        assert_eq!(results[0].label, "positive");
+        assert_eq!(results[0].words, 4);
+
+        let map = results[0].to_map();
+        assert_eq!(map["source"], "bbc.com");
+        assert_eq!(map["link"], Value::Null);
+        assert_eq!(map["sentiment"].as_f64(), Some(results[0].sentiment));
+    }
+
+    #[test]
+    fn ngrams_are_scored_once_each() {
+        let scores = ngram_scores(
+            ["Great win, great win!", "Storm kills dozens"],
+            2,
+            &Vader::default(),
+        );
+        let phrases: Vec<&str> = scores.iter().map(|(phrase, _)| phrase.as_str()).collect();
+        assert_eq!(phrases.len(), 4);
+        assert_eq!(phrases[0], "Great win");
+        assert!(phrases.contains(&"win great"));
+        assert!(scores.last().unwrap().1 < 0.0);
+    }
+
+    #[test]
+    fn lexicon_scores_only_the_words_it_knows() {
+        let lexicon = Lexicon::parse("# finance\nrally 2.0\nslump\t-3.0\t0.5\t[-3]\n").unwrap();
+        let polarity = lexicon.polarity("Stocks RALLY, then slump");
+        assert!(polarity.compound < 0.0);
+        assert_eq!(polarity.positive, 2.0 / 7.0);
+        assert_eq!(polarity.neutral, 2.0 / 7.0);
+        assert_eq!(lexicon.polarity("Nothing to see").compound, 0.0);
+        assert!(Lexicon::parse("rally high").is_err());
+    }
+
+    #[test]
+    fn lexicon_scores_must_be_finite_and_in_range() {
+        for bad in ["inf", "-inf", "NaN", "1e300", "4.5"] {
+            let error = Lexicon::parse(&format!("# finance\nrally {}\n", bad))
+                .err()
+                .unwrap_or_else(|| panic!("{} was accepted", bad));
+            assert!(error.starts_with("line 2: "), "{}", error);
+        }
+        assert!(Lexicon::parse("rally 4\nslump -4").is_ok());
+    }
+
+    #[test]
+    fn tokens_skip_stopwords_and_average_their_headlines() {
+        let tokens = token_frequencies([
+            ("Storm hits the coast: storm warnings issued", -0.6),
+            ("Storm's end brings relief to 2 towns", 0.4),
+        ]);
+        assert_eq!(tokens[0].0, "storm");
+        assert_eq!(tokens[0].1, 3);
+        assert!((tokens[0].2 - -0.1).abs() < 1e-9);
+        assert!(tokens
+            .iter()
+            .all(|(word, _, _)| word != "the" && word != "2"));
+    }
+}